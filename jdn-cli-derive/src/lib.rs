@@ -0,0 +1,544 @@
+#![deny(missing_docs)]
+//! Derive macros generating command line interface handlers for jdn-cli.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::spanned::Spanned;
+use syn::{
+    parse_macro_input, Attribute, Data, DeriveInput, Expr, Fields, GenericArgument, Lit, Path, PathArguments, Type,
+};
+
+/// Derives `jdn_cli::CliCommands` for an enum whose variants are commands, or a struct that is a single command.
+/// See the documentation of `jdn_cli::CliCommands` for the supported attributes.
+#[proc_macro_derive(CliCommands, attributes(cli))]
+pub fn derive_cli_commands(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input).unwrap_or_else(syn::Error::into_compile_error).into()
+}
+
+fn expand(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let ident = &input.ident;
+    let options = CliOptions::from_attributes(&input.attrs)?;
+    let commands = match &input.data {
+        Data::Enum(data) => data
+            .variants
+            .iter()
+            .map(|variant| {
+                let options = CliOptions::from_attributes(&variant.attrs)?;
+                let name = options
+                    .name
+                    .unwrap_or_else(|| to_kebab_case(&variant.ident.to_string()));
+                let variant_ident = &variant.ident;
+                let mut command = Command::new(name, quote!(Self::#variant_ident), &variant.fields, &variant.attrs)?;
+                command.aliases = options.aliases;
+                Ok(command)
+            })
+            .collect::<syn::Result<Vec<Command>>>()?,
+        Data::Struct(data) => {
+            let name = options
+                .name
+                .clone()
+                .unwrap_or_else(|| to_kebab_case(&ident.to_string()));
+            let mut command = Command::new(name, quote!(Self), &data.fields, &input.attrs)?;
+            command.aliases = options.aliases.clone();
+            vec![command]
+        }
+        Data::Union(_) => {
+            return Err(syn::Error::new(
+                input.span(),
+                "CliCommands cannot be derived for unions",
+            ))
+        }
+    };
+
+    let names: Vec<&String> = commands.iter().map(|command| &command.name).collect();
+    let specs: Vec<TokenStream2> = commands.iter().map(Command::spec).collect();
+    let builders: Vec<TokenStream2> = commands.iter().map(Command::builder).collect();
+    let summaries = help_arms(&commands, |command| command.summary.clone());
+    let descriptions = help_arms(&commands, |command| command.description.clone());
+    let aliases: Vec<TokenStream2> = commands
+        .iter()
+        .filter(|command| !command.aliases.is_empty())
+        .map(|command| {
+            let name = &command.name;
+            let aliases = &command.aliases;
+            quote!(#name => ::std::vec![#(#aliases),*],)
+        })
+        .collect();
+
+    let handler = options.handler.map(|handler| {
+        quote! {
+            impl ::jdn_cli::CliHandler for #handler {
+                fn get_commands(&self) -> ::std::collections::HashSet<&'static str> {
+                    <#ident as ::jdn_cli::CliCommands>::commands()
+                }
+
+                fn handle_command(
+                    &self,
+                    command: &str,
+                    args: ::std::vec::Vec<::std::string::String>,
+                    writer: &mut dyn ::std::io::Write,
+                ) -> ::std::result::Result<(), ::jdn_cli::CliError> {
+                    let command = <#ident as ::jdn_cli::CliCommands>::parse(command, args)?;
+                    ::jdn_cli::CliExecute::<#ident>::execute(self, command, writer)
+                }
+
+                fn get_spec(&self, command: &str) -> ::std::option::Option<::jdn_cli::spec::CommandSpec> {
+                    <#ident as ::jdn_cli::CliCommands>::spec(command)
+                }
+
+                fn handle_arguments(
+                    &self,
+                    command: &str,
+                    args: ::jdn_cli::spec::ParsedArgs,
+                    writer: &mut dyn ::std::io::Write,
+                ) -> ::std::result::Result<(), ::jdn_cli::CliError> {
+                    let command = <#ident as ::jdn_cli::CliCommands>::from_args(command, args)?;
+                    ::jdn_cli::CliExecute::<#ident>::execute(self, command, writer)
+                }
+
+                fn get_summary(&self, command: &str) -> ::std::option::Option<::std::string::String> {
+                    <#ident as ::jdn_cli::CliCommands>::summary(command)
+                }
+
+                fn get_description(&self, command: &str) -> ::std::option::Option<::std::string::String> {
+                    <#ident as ::jdn_cli::CliCommands>::description(command)
+                }
+
+                fn get_usage(&self, command: &str) -> ::std::option::Option<::std::string::String> {
+                    <#ident as ::jdn_cli::CliCommands>::usage(command)
+                }
+
+                fn get_aliases(&self, command: &str) -> ::std::vec::Vec<&'static str> {
+                    <#ident as ::jdn_cli::CliCommands>::aliases(command)
+                }
+            }
+        }
+    });
+
+    Ok(quote! {
+        impl ::jdn_cli::CliCommands for #ident {
+            fn commands() -> ::std::collections::HashSet<&'static str> {
+                [#(#names),*].iter().cloned().collect()
+            }
+
+            fn parse(
+                command: &str,
+                args: ::std::vec::Vec<::std::string::String>,
+            ) -> ::std::result::Result<Self, ::jdn_cli::CliError> {
+                match <Self as ::jdn_cli::CliCommands>::spec(command) {
+                    ::std::option::Option::Some(spec) => {
+                        <Self as ::jdn_cli::CliCommands>::from_args(command, spec.parse(args)?)
+                    }
+                    ::std::option::Option::None => ::std::result::Result::Err(::jdn_cli::CliError::ExecutionError(
+                        ::std::format!("Unknown command: {}", command),
+                    )),
+                }
+            }
+
+            fn spec(command: &str) -> ::std::option::Option<::jdn_cli::spec::CommandSpec> {
+                match command {
+                    #(#specs)*
+                    _ => ::std::option::Option::None,
+                }
+            }
+
+            #[allow(unused_variables)]
+            fn from_args(
+                command: &str,
+                args: ::jdn_cli::spec::ParsedArgs,
+            ) -> ::std::result::Result<Self, ::jdn_cli::CliError> {
+                match command {
+                    #(#builders)*
+                    _ => ::std::result::Result::Err(::jdn_cli::CliError::ExecutionError(
+                        ::std::format!("Unknown command: {}", command),
+                    )),
+                }
+            }
+
+            fn summary(command: &str) -> ::std::option::Option<::std::string::String> {
+                match command {
+                    #(#summaries)*
+                    _ => ::std::option::Option::None,
+                }
+            }
+
+            fn description(command: &str) -> ::std::option::Option<::std::string::String> {
+                match command {
+                    #(#descriptions)*
+                    _ => ::std::option::Option::None,
+                }
+            }
+
+            fn usage(command: &str) -> ::std::option::Option<::std::string::String> {
+                <Self as ::jdn_cli::CliCommands>::spec(command).map(|spec| spec.usage(command))
+            }
+
+            fn aliases(command: &str) -> ::std::vec::Vec<&'static str> {
+                match command {
+                    #(#aliases)*
+                    _ => ::std::vec::Vec::new(),
+                }
+            }
+        }
+
+        #handler
+    })
+}
+
+fn help_arms(commands: &[Command], text: impl Fn(&Command) -> Option<String>) -> Vec<TokenStream2> {
+    commands
+        .iter()
+        .filter_map(|command| {
+            let name = &command.name;
+            text(command).map(|text| quote!(#name => ::std::option::Option::Some(::std::string::String::from(#text)),))
+        })
+        .collect()
+}
+
+#[derive(Default)]
+struct CliOptions {
+    name: Option<String>,
+    aliases: Vec<String>,
+    handler: Option<Path>,
+}
+
+impl CliOptions {
+    fn from_attributes(attrs: &[Attribute]) -> syn::Result<Self> {
+        let mut options = CliOptions::default();
+        for attr in attrs.iter().filter(|attr| attr.path().is_ident("cli")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("name") {
+                    options.name = Some(meta.value()?.parse::<syn::LitStr>()?.value());
+                    Ok(())
+                } else if meta.path.is_ident("alias") {
+                    options.aliases.push(meta.value()?.parse::<syn::LitStr>()?.value());
+                    Ok(())
+                } else if meta.path.is_ident("handler") {
+                    options.handler = Some(meta.value()?.parse::<Path>()?);
+                    Ok(())
+                } else {
+                    Err(meta.error("expected `name`, `alias` or `handler`"))
+                }
+            })?;
+        }
+        Ok(options)
+    }
+}
+
+#[derive(Default)]
+struct FieldOptions {
+    flag: bool,
+    option: bool,
+    short: Option<char>,
+}
+
+impl FieldOptions {
+    fn from_attributes(attrs: &[Attribute]) -> syn::Result<Self> {
+        let mut options = FieldOptions::default();
+        for attr in attrs.iter().filter(|attr| attr.path().is_ident("cli")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("flag") {
+                    options.flag = true;
+                    Ok(())
+                } else if meta.path.is_ident("option") {
+                    options.option = true;
+                    Ok(())
+                } else if meta.path.is_ident("short") {
+                    options.short = Some(meta.value()?.parse::<syn::LitChar>()?.value());
+                    Ok(())
+                } else {
+                    Err(meta.error("expected `flag`, `option` or `short`"))
+                }
+            })?;
+        }
+        Ok(options)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Arity {
+    Required,
+    Optional,
+    Variadic,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Positional,
+    Flag,
+    Option,
+}
+
+struct Argument {
+    ident: Option<syn::Ident>,
+    name: String,
+    ty: Type,
+    arity: Arity,
+    kind: Kind,
+    short: Option<char>,
+}
+
+struct Command {
+    name: String,
+    constructor: TokenStream2,
+    named: bool,
+    args: Vec<Argument>,
+    summary: Option<String>,
+    description: Option<String>,
+    aliases: Vec<String>,
+}
+
+impl Command {
+    fn new(name: String, constructor: TokenStream2, fields: &Fields, attrs: &[Attribute]) -> syn::Result<Self> {
+        let mut args: Vec<Argument> = Vec::new();
+        for (index, field) in fields.iter().enumerate() {
+            let options = FieldOptions::from_attributes(&field.attrs)?;
+            let (arity, ty) = match inner_type(&field.ty, "Option") {
+                Some(ty) => (Arity::Optional, ty),
+                None => match inner_type(&field.ty, "Vec") {
+                    Some(ty) => (Arity::Variadic, ty),
+                    None => (Arity::Required, field.ty.clone()),
+                },
+            };
+            let kind = match (options.flag, options.option) {
+                (true, true) => {
+                    return Err(syn::Error::new(
+                        field.span(),
+                        "a field cannot be both a `flag` and an `option`",
+                    ))
+                }
+                (true, false) => Kind::Flag,
+                (false, true) => Kind::Option,
+                (false, false) => Kind::Positional,
+            };
+            if kind == Kind::Flag && !is_bool(&field.ty) {
+                return Err(syn::Error::new(field.span(), "a `flag` field must be a `bool`"));
+            }
+            if kind == Kind::Option && arity == Arity::Required {
+                return Err(syn::Error::new(
+                    field.span(),
+                    "an `option` field must be an `Option` or a `Vec`",
+                ));
+            }
+            if kind != Kind::Positional && field.ident.is_none() {
+                return Err(syn::Error::new(
+                    field.span(),
+                    "a `flag` or `option` field must be named",
+                ));
+            }
+            if kind == Kind::Positional && options.short.is_some() {
+                return Err(syn::Error::new(
+                    field.span(),
+                    "`short` requires a `flag` or `option` field",
+                ));
+            }
+
+            if kind == Kind::Positional {
+                let previous = args
+                    .iter()
+                    .rfind(|arg| arg.kind == Kind::Positional)
+                    .map(|arg| arg.arity);
+                if previous == Some(Arity::Variadic) {
+                    return Err(syn::Error::new(
+                        field.span(),
+                        "a `Vec` argument must be the last argument",
+                    ));
+                }
+                if arity == Arity::Required && previous == Some(Arity::Optional) {
+                    return Err(syn::Error::new(
+                        field.span(),
+                        "required arguments must precede `Option` arguments",
+                    ));
+                }
+            }
+            let name = match &field.ident {
+                Some(ident) if kind == Kind::Positional => ident.to_string(),
+                Some(ident) => to_kebab_case(&ident.to_string()),
+                None => format!("arg{}", index + 1),
+            };
+            args.push(Argument {
+                ident: field.ident.clone(),
+                name,
+                ty,
+                arity,
+                kind,
+                short: options.short,
+            });
+        }
+
+        let paragraphs: Vec<String> = doc_lines(attrs)
+            .split(|line| line.is_empty())
+            .filter(|paragraph| !paragraph.is_empty())
+            .map(|paragraph| paragraph.join(" "))
+            .collect();
+        let summary = paragraphs.first().cloned();
+        let description = Some(paragraphs.join("\n\n")).filter(|description| !description.is_empty());
+
+        Ok(Command {
+            name,
+            constructor,
+            named: matches!(fields, Fields::Named(_)),
+            args,
+            summary,
+            description,
+            aliases: Vec::new(),
+        })
+    }
+
+    fn spec(&self) -> TokenStream2 {
+        let name = &self.name;
+        let args = self.args.iter().filter(|arg| arg.kind == Kind::Positional).map(|arg| {
+            let arg_name = &arg.name;
+            let arity = match arg.arity {
+                Arity::Required => quote!(),
+                Arity::Optional => quote!(.optional()),
+                Arity::Variadic => quote!(.variadic()),
+            };
+            quote!(.arg(::jdn_cli::spec::ArgSpec::new(#arg_name, ::jdn_cli::spec::ArgType::String)#arity))
+        });
+        let options = self.args.iter().filter(|arg| arg.kind != Kind::Positional).map(|arg| {
+            let option_name = &arg.name;
+            let option = if arg.kind == Kind::Flag {
+                quote!(::jdn_cli::spec::OptionSpec::flag(#option_name))
+            } else {
+                quote!(::jdn_cli::spec::OptionSpec::value(#option_name, ::jdn_cli::spec::ArgType::String))
+            };
+            match arg.short {
+                Some(short) => quote!(.option(#option.short(#short))),
+                None => quote!(.option(#option)),
+            }
+        });
+        quote! {
+            #name => ::std::option::Option::Some(::jdn_cli::spec::CommandSpec::new()#(#options)*#(#args)*),
+        }
+    }
+
+    fn builder(&self) -> TokenStream2 {
+        let name = &self.name;
+        let values: Vec<TokenStream2> = self
+            .args
+            .iter()
+            .map(|arg| {
+                let arg_name = &arg.name;
+                let convert = convert(arg);
+                let value = match (arg.kind, arg.arity) {
+                    (Kind::Flag, _) => quote!(args.has_option(#arg_name)),
+                    (_, Arity::Required) => quote! {
+                        {
+                            let value = args.get_str(#arg_name).unwrap_or_default();
+                            #convert?
+                        }
+                    },
+                    (_, Arity::Optional) => quote! {
+                        match args.get_str(#arg_name) {
+                            ::std::option::Option::Some(value) => ::std::option::Option::Some(#convert?),
+                            ::std::option::Option::None => ::std::option::Option::None,
+                        }
+                    },
+                    (_, Arity::Variadic) => quote! {
+                        args.get_all(#arg_name)
+                            .iter()
+                            .filter_map(|value| match value {
+                                ::jdn_cli::spec::ArgValue::String(value) => ::std::option::Option::Some(value.as_str()),
+                                _ => ::std::option::Option::None,
+                            })
+                            .map(|value| #convert)
+                            .collect::<::std::result::Result<::std::vec::Vec<_>, ::jdn_cli::CliError>>()?
+                    },
+                };
+                match &arg.ident {
+                    Some(ident) => quote!(#ident: #value),
+                    None => value,
+                }
+            })
+            .collect();
+
+        let constructor = &self.constructor;
+        let construct = if self.args.is_empty() {
+            quote!(#constructor {})
+        } else if self.named {
+            quote!(#constructor { #(#values),* })
+        } else {
+            quote!(#constructor(#(#values),*))
+        };
+
+        quote! {
+            #name => ::std::result::Result::Ok(#construct),
+        }
+    }
+}
+
+fn convert(arg: &Argument) -> TokenStream2 {
+    let ty = &arg.ty;
+    let label = match arg.kind {
+        Kind::Positional => format!("<{}>", arg.name),
+        _ => format!("--{}", arg.name),
+    };
+    quote! {
+        <#ty as ::std::str::FromStr>::from_str(value).map_err(|e| {
+            ::jdn_cli::CliError::ArgumentParseFailure(::std::format!(
+                "invalid value '{}' for {}: {}",
+                value,
+                #label,
+                e
+            ))
+        })
+    }
+}
+
+fn is_bool(ty: &Type) -> bool {
+    match ty {
+        Type::Path(path) => path.qself.is_none() && path.path.is_ident("bool"),
+        _ => false,
+    }
+}
+
+fn inner_type(ty: &Type, wrapper: &str) -> Option<Type> {
+    let segment = match ty {
+        Type::Path(path) if path.qself.is_none() => path.path.segments.last()?,
+        _ => return None,
+    };
+    if segment.ident != wrapper {
+        return None;
+    }
+    match &segment.arguments {
+        PathArguments::AngleBracketed(args) if args.args.len() == 1 => match args.args.first()? {
+            GenericArgument::Type(ty) => Some(ty.clone()),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn doc_lines(attrs: &[Attribute]) -> Vec<String> {
+    attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("doc"))
+        .filter_map(|attr| match &attr.meta.require_name_value().ok()?.value {
+            Expr::Lit(expr) => match &expr.lit {
+                Lit::Str(doc) => Some(doc.value().trim().to_owned()),
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect()
+}
+
+fn to_kebab_case(ident: &str) -> String {
+    let mut name = String::new();
+    for (index, c) in ident.chars().enumerate() {
+        if c.is_uppercase() {
+            if index > 0 {
+                name.push('-');
+            }
+            name.extend(c.to_lowercase());
+        } else if c == '_' {
+            name.push('-');
+        } else {
+            name.push(c);
+        }
+    }
+    name
+}
@@ -0,0 +1,126 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// The character separating the name of an alias from the command it stands for, e.g. `ls=list --all`.
+pub const ALIAS_SEPARATOR: char = '=';
+
+/// The aliases defined by the user, optionally persisted to a file. Each alias stands for a command line, which
+/// replaces the name of the alias when it is the first word of a command, e.g. `ls` for `list --all`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Aliases {
+    entries: BTreeMap<String, String>,
+    file: Option<PathBuf>,
+}
+
+impl Aliases {
+    /// Constructs a new, empty Aliases that is kept in memory only.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Constructs a new Aliases that is persisted to the given file, loading any aliases it already contains. Each
+    /// line of the file is an alias in the form `name=command`. The file is rewritten each time an alias is defined
+    /// or removed.
+    ///
+    /// # Arguments
+    /// `path` - The path of the file, which is created when the first alias is defined if it does not exist.
+    ///
+    /// # Errors
+    /// An io::Error if the file exists but could not be read.
+    pub fn with_file<P: Into<PathBuf>>(path: P) -> io::Result<Self> {
+        let path = path.into();
+        let mut aliases = Aliases::new();
+        match fs::read_to_string(&path) {
+            Ok(contents) => {
+                for (name, command) in contents.lines().filter_map(|line| line.split_once(ALIAS_SEPARATOR)) {
+                    aliases.entries.insert(name.to_owned(), command.to_owned());
+                }
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+        aliases.file = Some(path);
+        Ok(aliases)
+    }
+
+    /// Gets the names of the aliases and the command lines they stand for, sorted by name.
+    pub fn entries(&self) -> &BTreeMap<String, String> {
+        &self.entries
+    }
+
+    /// Gets the command line the given alias stands for.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.entries.get(name).map(String::as_str)
+    }
+
+    /// Gets the number of aliases.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Gets whether no aliases have been defined.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Gets the file to which the aliases are persisted, if any.
+    pub fn file(&self) -> Option<&Path> {
+        self.file.as_deref()
+    }
+
+    /// Defines the given alias, replacing any alias with the same name.
+    ///
+    /// # Arguments
+    /// `name` - The name of the alias, which must not be empty or contain [ALIAS_SEPARATOR] or a line break.
+    /// `command` - The command line the alias stands for, which must not contain a line break.
+    ///
+    /// # Errors
+    /// An io::Error of kind [io::ErrorKind::InvalidInput] if the name or command is not allowed, in which case the
+    /// alias is not defined, or an io::Error if the aliases could not be written to their file, in which case the alias
+    /// is defined in memory regardless.
+    pub fn set(&mut self, name: &str, command: &str) -> io::Result<()> {
+        let line_break = |c| c == '\n' || c == '\r';
+        if name.is_empty()
+            || name.contains(ALIAS_SEPARATOR)
+            || name.contains(line_break)
+            || command.contains(line_break)
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid alias {}{}{}", name, ALIAS_SEPARATOR, command),
+            ));
+        }
+        self.entries.insert(name.to_owned(), command.to_owned());
+        self.save()
+    }
+
+    /// Removes the given alias.
+    ///
+    /// # Returns
+    /// True if the alias was defined.
+    ///
+    /// # Errors
+    /// An io::Error if the aliases could not be written to their file.
+    pub fn remove(&mut self, name: &str) -> io::Result<bool> {
+        if self.entries.remove(name).is_none() {
+            return Ok(false);
+        }
+        self.save().map(|_| true)
+    }
+
+    fn save(&self) -> io::Result<()> {
+        match &self.file {
+            Some(path) => {
+                let contents: String = self
+                    .entries
+                    .iter()
+                    .map(|(name, command)| format!("{}{}{}\n", name, ALIAS_SEPARATOR, command))
+                    .collect();
+                fs::write(path, contents)
+            }
+            None => Ok(()),
+        }
+    }
+}
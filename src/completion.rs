@@ -0,0 +1,141 @@
+use std::fs;
+use std::path::{Path, MAIN_SEPARATOR};
+use std::vec::Vec;
+
+use crate::tokenizer;
+use crate::tokenizer::{DOUBLE_QUOTE, ESCAPE, SINGLE_QUOTE};
+
+/// The hidden command used by shell completion scripts to request completions from a one-shot invocation of the
+/// program. The remaining arguments are the words of the command line up to and including the word being completed.
+pub const COMPLETE_COMMAND: &str = "__complete";
+
+/// The candidate completions of the word at the end of a command line.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Completions {
+    start: usize,
+    candidates: Vec<String>,
+}
+
+impl Completions {
+    /// Constructs a new Completions.
+    ///
+    /// # Arguments
+    /// `start` - The byte offset in the command line at which the word being completed starts.
+    /// `candidates` - The words that may replace the word being completed.
+    pub fn new(start: usize, candidates: Vec<String>) -> Self {
+        Completions { start, candidates }
+    }
+
+    /// Gets the byte offset in the command line at which the word being completed starts.
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    /// Gets the words that may replace the word being completed, sorted and without quotes. Use
+    /// [tokenizer::quote()] before inserting a candidate into a command line.
+    pub fn candidates(&self) -> &[String] {
+        &self.candidates
+    }
+
+    /// Gets the longest prefix shared by all candidates, or `None` if there are no candidates.
+    pub fn common_prefix(&self) -> Option<&str> {
+        let (first, rest) = self.candidates.split_first()?;
+        let mut prefix = first.as_str();
+        for candidate in rest {
+            let len = prefix
+                .char_indices()
+                .zip(candidate.chars())
+                .find(|((_, a), b)| a != b)
+                .map_or_else(|| prefix.len().min(candidate.len()), |((index, _), _)| index);
+            prefix = &prefix[..len];
+        }
+        Some(prefix)
+    }
+}
+
+/// Completes the given partial file system path, e.g. for use in [crate::CliHandler::complete_argument()].
+/// Directories are completed with a trailing path separator. Returns an empty Vec if the directory cannot be read.
+pub fn complete_path(partial: &str) -> Vec<String> {
+    let (dir, prefix) = match partial.rfind(['/', MAIN_SEPARATOR]) {
+        Some(index) => (&partial[..=index], &partial[index + 1..]),
+        None => ("", partial),
+    };
+    let entries = match fs::read_dir(if dir.is_empty() { Path::new(".") } else { Path::new(dir) }) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+    let mut candidates: Vec<String> = entries
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            if !name.starts_with(prefix) || (name.starts_with('.') && !prefix.starts_with('.')) {
+                return None;
+            }
+            let separator = if entry.path().is_dir() { "/" } else { "" };
+            Some(format!("{}{}{}", dir, name, separator))
+        })
+        .collect();
+    candidates.sort();
+    candidates
+}
+
+/// Generates a bash script that completes the commands of the given program, which must pass its process arguments
+/// to [crate::manager::CliManager::run_args()]. The script is typically sourced from `~/.bashrc`, e.g.
+/// `source <(mytool completion-script)`.
+pub fn bash_completion_script(program: &str) -> String {
+    let function = format!(
+        "_{}_complete",
+        program.replace(|c: char| !c.is_ascii_alphanumeric(), "_")
+    );
+    format!(
+        concat!(
+            "{function}() {{\n",
+            "    local IFS=$'\\n'\n",
+            "    COMPREPLY=($(\"{program}\" {complete} \"${{COMP_WORDS[@]:1:COMP_CWORD}}\" 2>/dev/null))\n",
+            "}}\n",
+            "complete -o filenames -F {function} {program}\n",
+        ),
+        function = function,
+        program = program,
+        complete = COMPLETE_COMMAND
+    )
+}
+
+/// Splits the last command of the given command line into the complete words preceding the word being completed, the
+/// unquoted text of the word being completed, and the byte offset at which that word starts.
+pub(crate) fn split_line(line: &str) -> (Vec<String>, String, usize) {
+    let start = word_start(line);
+    let words = tokenizer::last_command(&line[..start]);
+    let partial = &line[start..];
+    let unquoted = ["", "\"", "'"]
+        .iter()
+        .find_map(|suffix| tokenizer::tokenize(&format!("{}{}", partial, suffix)).ok())
+        .or_else(|| tokenizer::tokenize(partial.trim_end_matches(ESCAPE)).ok())
+        .and_then(|tokens| tokens.into_iter().next())
+        .unwrap_or_default();
+    (words, unquoted, start)
+}
+
+fn word_start(line: &str) -> usize {
+    let mut start = 0;
+    let mut quote = None;
+    let mut escaped = false;
+    for (index, c) in line.char_indices() {
+        if escaped {
+            escaped = false;
+            continue;
+        }
+        match quote {
+            Some(open) if c == open => quote = None,
+            Some(DOUBLE_QUOTE) if c == ESCAPE => escaped = true,
+            Some(_) => {}
+            None => match c {
+                ESCAPE => escaped = true,
+                SINGLE_QUOTE | DOUBLE_QUOTE => quote = Some(c),
+                c if c.is_whitespace() || tokenizer::is_operator_char(c) => start = index + c.len_utf8(),
+                _ => {}
+            },
+        }
+    }
+    start
+}
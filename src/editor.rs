@@ -0,0 +1,713 @@
+use std::cmp;
+use std::io;
+use std::io::{BufRead, IsTerminal, Read, Stdin, Stdout, Write};
+use std::path::MAIN_SEPARATOR;
+use std::time::Duration;
+use std::vec::Vec;
+
+use crate::completion;
+use crate::completion::Completions;
+use crate::history::DEFAULT_HISTORY_SIZE;
+use crate::tokenizer;
+
+const SEARCH_PROMPT: &str = "(reverse-i-search)";
+// the rest of an escape sequence is sent along with the Escape that starts it
+const ESCAPE_TIMEOUT: Duration = Duration::from_millis(50);
+const RECEIVE_SIZE: usize = 1024;
+const BELL: &str = "\x07";
+const ESCAPE: u8 = 0x1b;
+const CTRL_A: u8 = 0x01;
+const CTRL_B: u8 = 0x02;
+const CTRL_C: u8 = 0x03;
+const CTRL_D: u8 = 0x04;
+const CTRL_E: u8 = 0x05;
+const CTRL_F: u8 = 0x06;
+const CTRL_G: u8 = 0x07;
+const CTRL_H: u8 = 0x08;
+const TAB: u8 = 0x09;
+const CTRL_K: u8 = 0x0b;
+const CTRL_N: u8 = 0x0e;
+const CTRL_P: u8 = 0x10;
+const CTRL_R: u8 = 0x12;
+const CTRL_U: u8 = 0x15;
+const CTRL_W: u8 = 0x17;
+const DELETE: u8 = 0x7f;
+
+/// A reader that lets the user edit each line before it is submitted, for use in place of a plain reader in a
+/// [crate::manager::CliManager]. The following keys are supported while editing:
+/// - Left/Right or Ctrl-B/Ctrl-F move the cursor by one character
+/// - Ctrl-Left/Ctrl-Right or Alt-B/Alt-F move the cursor by one word
+/// - Home/End or Ctrl-A/Ctrl-E move the cursor to the start or end of the line
+/// - Backspace and Delete remove the character before or under the cursor
+/// - Ctrl-U, Ctrl-K and Ctrl-W remove the text before the cursor, after the cursor, or the word before the cursor
+/// - Up/Down or Ctrl-P/Ctrl-N recall the previous or next line of the history
+/// - Ctrl-R searches the history backwards for lines containing the typed text; Ctrl-G or Escape cancels the search
+/// - Tab completes the word before the cursor, when lines are read with [LineEditor::read_line_with()]
+/// - Ctrl-C discards the line, and Ctrl-D ends the input when the line is empty
+///
+/// When editing is disabled, lines are read unchanged from the input, as done by a plain reader.
+pub struct LineEditor<I: Read, O: Write> {
+    input: I,
+    output: O,
+    editing: bool,
+    raw_mode: bool,
+    history: Vec<String>,
+    history_size: usize,
+    pending: Vec<u8>,
+    position: usize,
+    received: Vec<u8>,
+    received_position: usize,
+}
+
+impl LineEditor<Stdin, Stdout> {
+    /// Constructs a new LineEditor for the standard input and output streams. Editing is enabled only if both streams
+    /// are terminals, in which case the terminal is placed in raw mode while a line is being edited; otherwise lines
+    /// are read unchanged.
+    pub fn stdio() -> Self {
+        let (input, output) = (io::stdin(), io::stdout());
+        let editing = cfg!(unix) && input.is_terminal() && output.is_terminal();
+        let mut editor = LineEditor::new(input, output);
+        editor.editing = editing;
+        editor.raw_mode = editing;
+        editor
+    }
+}
+
+impl<I: Read, O: Write> LineEditor<I, O> {
+    /// Constructs a new LineEditor with editing enabled. Input is expected to be delivered as it is typed, and edits
+    /// are echoed to the given output stream.
+    ///
+    /// # Arguments
+    /// `input` - The stream from which key presses are read.
+    /// `output` - The stream to which the line being edited is displayed.
+    pub fn new(input: I, output: O) -> Self {
+        LineEditor {
+            input,
+            output,
+            editing: true,
+            raw_mode: false,
+            history: Vec::new(),
+            history_size: DEFAULT_HISTORY_SIZE,
+            pending: Vec::new(),
+            position: 0,
+            received: Vec::new(),
+            received_position: 0,
+        }
+    }
+
+    /// Gets whether lines are edited before they are submitted, or read unchanged from the input.
+    pub fn is_editing(&self) -> bool {
+        self.editing
+    }
+
+    /// Sets whether lines are edited before they are submitted, or read unchanged from the input.
+    ///
+    /// # Arguments
+    /// `editing` - True to edit lines, false to read lines unchanged.
+    pub fn set_editing(&mut self, editing: bool) {
+        self.editing = editing;
+    }
+
+    /// Gets the lines that can be recalled while editing, from oldest to newest.
+    pub fn history(&self) -> &[String] {
+        &self.history
+    }
+
+    /// Adds the given line to the end of the history. Blank lines and repeats of the newest line are ignored, and
+    /// the oldest lines are discarded once the history exceeds its maximum size.
+    ///
+    /// # Arguments
+    /// `line` - The line to add.
+    pub fn add_history(&mut self, line: &str) {
+        if line.trim().is_empty() || self.history.last().is_some_and(|last| last == line) {
+            return;
+        }
+        self.history.push(line.to_owned());
+        self.truncate_history();
+    }
+
+    /// Sets the maximum number of lines retained in the history, discarding the oldest lines if necessary.
+    ///
+    /// # Arguments
+    /// `size` - The maximum number of lines, which defaults to [DEFAULT_HISTORY_SIZE].
+    pub fn set_history_size(&mut self, size: usize) {
+        self.history_size = size;
+        self.truncate_history();
+    }
+
+    /// Reads a line like [BufRead::read_line()], recalling lines from the given history rather than the history of
+    /// the editor, and completing the word before the cursor with the given completer when Tab is pressed while
+    /// editing: a single candidate replaces the word, and several candidates extend it to their longest common
+    /// prefix. The line is not added to the history of the editor, so that the caller can record it in its own.
+    ///
+    /// # Arguments
+    /// `buf` - The buffer to which the line is appended, along with its line break.
+    /// `history` - The lines that can be recalled, from oldest to newest, e.g. [crate::history::History::entries()].
+    /// `completer` - Gets the completions of the last word of the given text before the cursor, e.g.
+    /// [crate::manager::CliManager::complete()].
+    ///
+    /// # Returns
+    /// The number of bytes appended, which is 0 once the input has ended.
+    pub fn read_line_with(
+        &mut self,
+        buf: &mut String,
+        history: &[String],
+        completer: &mut dyn FnMut(&str) -> Completions,
+    ) -> io::Result<usize> {
+        if self.position < self.pending.len() {
+            return self.read_line(buf);
+        }
+        let line = if self.editing {
+            self.read_line_edited(Some(history), Some(completer))?
+        } else {
+            self.read_line_unedited()?
+        };
+        Ok(line.map_or(0, |line| {
+            buf.push_str(&line);
+            buf.push('\n');
+            line.len() + 1
+        }))
+    }
+
+    fn truncate_history(&mut self) {
+        let excess = self.history.len().saturating_sub(self.history_size);
+        self.history.drain(..excess);
+    }
+
+    fn read_line_unedited(&mut self) -> io::Result<Option<String>> {
+        let mut line = Vec::new();
+        loop {
+            match self.read_byte()? {
+                None if line.is_empty() => return Ok(None),
+                None | Some(b'\n') => break,
+                Some(byte) => line.push(byte),
+            }
+        }
+        Ok(Some(String::from_utf8_lossy(&line).into_owned()))
+    }
+
+    /// Reads a line while editing, recalling lines from the given history, or the history of the editor if none is
+    /// given, in which case the line is added to it.
+    fn read_line_edited(
+        &mut self,
+        history: Option<&[String]>,
+        mut completer: Option<&mut dyn FnMut(&str) -> Completions>,
+    ) -> io::Result<Option<String>> {
+        let _raw_mode = if self.raw_mode { Some(RawMode::enable()?) } else { None };
+        let mut session = Session::default();
+        loop {
+            let key = match self.read_key()? {
+                Some(key) => key,
+                None if session.line.is_empty() => return Ok(None),
+                None => Key::Enter,
+            };
+            if let (Key::Complete, None, Some(completer)) = (key, &session.search, completer.as_mut()) {
+                let before: String = session.line[..session.cursor].iter().collect();
+                if !session.complete(&before, &completer(&before)) {
+                    write!(self.output, "{}", BELL)?;
+                }
+                session.render(history.unwrap_or(&self.history), &mut self.output)?;
+                continue;
+            }
+            match session.handle(key, history.unwrap_or(&self.history)) {
+                Some(Submit::Line) => {
+                    session.render(history.unwrap_or(&self.history), &mut self.output)?;
+                    writeln!(self.output)?;
+                    self.output.flush()?;
+                    let line: String = session.line.into_iter().collect();
+                    if history.is_none() {
+                        self.add_history(&line);
+                    }
+                    return Ok(Some(line));
+                }
+                Some(Submit::Interrupt) => {
+                    writeln!(self.output, "^C")?;
+                    self.output.flush()?;
+                    return Ok(Some(String::new()));
+                }
+                Some(Submit::EndOfInput) => {
+                    writeln!(self.output)?;
+                    self.output.flush()?;
+                    return Ok(None);
+                }
+                None => session.render(history.unwrap_or(&self.history), &mut self.output)?,
+            }
+        }
+    }
+
+    fn read_byte(&mut self) -> io::Result<Option<u8>> {
+        if self.received_position >= self.received.len() {
+            self.received.resize(RECEIVE_SIZE, 0);
+            let len = loop {
+                // the standard input is read directly in raw mode, as its buffer would hide pending bytes from poll
+                let result = if self.raw_mode {
+                    read_stdin(&mut self.received)
+                } else {
+                    self.input.read(&mut self.received)
+                };
+                match result {
+                    Ok(len) => break len,
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                    Err(e) => {
+                        self.received.clear();
+                        return Err(e);
+                    }
+                }
+            };
+            self.received.truncate(len);
+            self.received_position = 0;
+            if len == 0 {
+                return Ok(None);
+            }
+        }
+        self.received_position += 1;
+        Ok(Some(self.received[self.received_position - 1]))
+    }
+
+    fn read_key(&mut self) -> io::Result<Option<Key>> {
+        let byte = match self.read_byte()? {
+            Some(byte) => byte,
+            None => return Ok(None),
+        };
+        let key = match byte {
+            b'\r' | b'\n' => Key::Enter,
+            CTRL_A => Key::Home,
+            CTRL_B => Key::Left,
+            CTRL_C => Key::Interrupt,
+            CTRL_D => Key::EndOfInput,
+            CTRL_E => Key::End,
+            CTRL_F => Key::Right,
+            CTRL_G => Key::Cancel,
+            CTRL_H | DELETE => Key::Backspace,
+            TAB => Key::Complete,
+            CTRL_K => Key::KillEnd,
+            CTRL_N => Key::Down,
+            CTRL_P => Key::Up,
+            CTRL_R => Key::Search,
+            CTRL_U => Key::KillStart,
+            CTRL_W => Key::KillWord,
+            ESCAPE => self.read_escape()?,
+            byte if byte < 0x20 => Key::Ignored,
+            byte => self.read_char(byte)?,
+        };
+        Ok(Some(key))
+    }
+
+    fn read_escape(&mut self) -> io::Result<Key> {
+        let received = self.received_position < self.received.len();
+        if !received && self.raw_mode && !input_ready(ESCAPE_TIMEOUT)? {
+            return Ok(Key::Cancel);
+        }
+        let key = match self.read_byte()? {
+            Some(b'[') | Some(b'O') => {
+                let mut params = Vec::new();
+                loop {
+                    match self.read_byte()? {
+                        Some(byte) if byte.is_ascii_digit() || byte == b';' => params.push(byte),
+                        Some(byte) => break control_sequence(&params, byte),
+                        None => break Key::Ignored,
+                    }
+                }
+            }
+            Some(b'b') => Key::WordLeft,
+            Some(b'f') => Key::WordRight,
+            byte => {
+                // a bare Escape leaves the following key to be handled on its own
+                if byte.is_some() {
+                    self.received_position -= 1;
+                }
+                Key::Cancel
+            }
+        };
+        Ok(key)
+    }
+
+    fn read_char(&mut self, first: u8) -> io::Result<Key> {
+        let len = match first {
+            0xf0..=0xff => 4,
+            0xe0..=0xef => 3,
+            0xc0..=0xdf => 2,
+            _ => 1,
+        };
+        let mut bytes = vec![first];
+        for _ in 1..len {
+            match self.read_byte()? {
+                Some(byte) => bytes.push(byte),
+                None => break,
+            }
+        }
+        let key = match std::str::from_utf8(&bytes).ok().and_then(|text| text.chars().next()) {
+            Some(c) => Key::Char(c),
+            None => Key::Ignored,
+        };
+        Ok(key)
+    }
+}
+
+impl<I: Read, O: Write> Read for LineEditor<I, O> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let available = self.fill_buf()?;
+        let len = cmp::min(available.len(), buf.len());
+        buf[..len].copy_from_slice(&available[..len]);
+        self.consume(len);
+        Ok(len)
+    }
+}
+
+impl<I: Read, O: Write> BufRead for LineEditor<I, O> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if self.position >= self.pending.len() {
+            self.pending.clear();
+            self.position = 0;
+            let line = if self.editing {
+                self.read_line_edited(None, None)?
+            } else {
+                self.read_line_unedited()?
+            };
+            if let Some(line) = line {
+                self.pending.extend_from_slice(line.as_bytes());
+                self.pending.push(b'\n');
+            }
+        }
+        Ok(&self.pending[self.position..])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.position = cmp::min(self.position + amt, self.pending.len());
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Key {
+    Char(char),
+    Enter,
+    Backspace,
+    Delete,
+    Left,
+    Right,
+    WordLeft,
+    WordRight,
+    Home,
+    End,
+    Up,
+    Down,
+    KillStart,
+    KillEnd,
+    KillWord,
+    Search,
+    Complete,
+    Cancel,
+    Interrupt,
+    EndOfInput,
+    Ignored,
+}
+
+fn control_sequence(params: &[u8], last: u8) -> Key {
+    // modifiers are reported as a second parameter, e.g. `1;5C` for Ctrl-Right
+    let modified = params.contains(&b';');
+    match (last, params) {
+        (b'A', _) => Key::Up,
+        (b'B', _) => Key::Down,
+        (b'C', _) if modified => Key::WordRight,
+        (b'C', _) => Key::Right,
+        (b'D', _) if modified => Key::WordLeft,
+        (b'D', _) => Key::Left,
+        (b'H', _) | (b'~', b"1") | (b'~', b"7") => Key::Home,
+        (b'F', _) | (b'~', b"4") | (b'~', b"8") => Key::End,
+        (b'~', b"3") => Key::Delete,
+        _ => Key::Ignored,
+    }
+}
+
+enum Submit {
+    Line,
+    Interrupt,
+    EndOfInput,
+}
+
+struct Search {
+    query: String,
+    found: Option<usize>,
+}
+
+impl Search {
+    /// Finds the newest line of the history before the given end that contains the query.
+    fn find(&mut self, history: &[String], end: Option<usize>) {
+        let end = end.unwrap_or(history.len());
+        if let Some(found) = history[..end].iter().rposition(|line| line.contains(&self.query)) {
+            self.found = Some(found);
+        }
+    }
+}
+
+#[derive(Default)]
+struct Session {
+    line: Vec<char>,
+    cursor: usize,
+    drawn: usize,
+    recalled: Option<usize>,
+    draft: Vec<char>,
+    search: Option<Search>,
+}
+
+impl Session {
+    fn handle(&mut self, key: Key, history: &[String]) -> Option<Submit> {
+        if let Some(mut search) = self.search.take() {
+            let end = match key {
+                Key::Char(c) => {
+                    search.query.push(c);
+                    search.found.map(|found| found + 1)
+                }
+                Key::Backspace => {
+                    search.query.pop();
+                    None
+                }
+                Key::Search => search.found,
+                Key::Cancel => return None,
+                Key::Interrupt => return Some(Submit::Interrupt),
+                key => {
+                    // any other key accepts the line that was found and is then handled as usual
+                    self.recall(history, search.found);
+                    return self.handle(key, history);
+                }
+            };
+            search.find(history, end);
+            self.search = Some(search);
+            return None;
+        }
+        match key {
+            Key::Char(c) => {
+                self.line.insert(self.cursor, c);
+                self.cursor += 1;
+            }
+            Key::Enter => return Some(Submit::Line),
+            Key::Backspace if self.cursor > 0 => {
+                self.cursor -= 1;
+                self.line.remove(self.cursor);
+            }
+            Key::Delete if self.cursor < self.line.len() => {
+                self.line.remove(self.cursor);
+            }
+            Key::Left => self.cursor = self.cursor.saturating_sub(1),
+            Key::Right => self.cursor = cmp::min(self.cursor + 1, self.line.len()),
+            Key::WordLeft => self.cursor = self.word_start(),
+            Key::WordRight => self.cursor = self.word_end(),
+            Key::Home => self.cursor = 0,
+            Key::End => self.cursor = self.line.len(),
+            Key::Up => self.recall(history, self.recalled.unwrap_or(history.len()).checked_sub(1)),
+            Key::Down => match self.recalled {
+                Some(index) if index + 1 < history.len() => self.recall(history, Some(index + 1)),
+                Some(_) => {
+                    self.recalled = None;
+                    let draft = std::mem::take(&mut self.draft);
+                    self.set_line(draft);
+                }
+                None => {}
+            },
+            Key::KillStart => {
+                self.line.drain(..self.cursor);
+                self.cursor = 0;
+            }
+            Key::KillEnd => self.line.truncate(self.cursor),
+            Key::KillWord => {
+                let start = self.word_start();
+                self.line.drain(start..self.cursor);
+                self.cursor = start;
+            }
+            Key::Search => {
+                self.search = Some(Search {
+                    query: String::new(),
+                    found: None,
+                })
+            }
+            Key::Interrupt => return Some(Submit::Interrupt),
+            Key::EndOfInput if self.line.is_empty() => return Some(Submit::EndOfInput),
+            Key::EndOfInput if self.cursor < self.line.len() => {
+                self.line.remove(self.cursor);
+            }
+            _ => {}
+        }
+        None
+    }
+
+    /// Replaces the word before the cursor with the single candidate of the given completions, followed by a space
+    /// unless it is a directory, or extends it to the longest common prefix of several candidates.
+    ///
+    /// # Arguments
+    /// `before` - The text before the cursor, of which the completions were requested.
+    /// `completions` - The completions of the last word of the text.
+    ///
+    /// # Returns
+    /// False if the word could not be completed or extended.
+    fn complete(&mut self, before: &str, completions: &Completions) -> bool {
+        let (_, partial, _) = completion::split_line(before);
+        let replacement = match completions.candidates() {
+            [candidate] if candidate.ends_with(['/', MAIN_SEPARATOR]) => tokenizer::quote(candidate),
+            [candidate] => format!("{} ", tokenizer::quote(candidate)),
+            _ => match completions.common_prefix() {
+                Some(prefix) if prefix.chars().count() > partial.chars().count() => tokenizer::quote(prefix),
+                _ => return false,
+            },
+        };
+        let start = before
+            .get(..completions.start())
+            .map_or(self.cursor, |text| text.chars().count());
+        self.line.splice(start..self.cursor, replacement.chars());
+        self.cursor = start + replacement.chars().count();
+        true
+    }
+
+    fn recall(&mut self, history: &[String], index: Option<usize>) {
+        if let Some(index) = index {
+            if self.recalled.is_none() {
+                self.draft = self.line.clone();
+            }
+            self.recalled = Some(index);
+            self.set_line(history[index].chars().collect());
+        }
+    }
+
+    fn set_line(&mut self, line: Vec<char>) {
+        self.line = line;
+        self.cursor = self.line.len();
+    }
+
+    fn word_start(&self) -> usize {
+        let mut index = self.cursor;
+        while index > 0 && self.line[index - 1].is_whitespace() {
+            index -= 1;
+        }
+        while index > 0 && !self.line[index - 1].is_whitespace() {
+            index -= 1;
+        }
+        index
+    }
+
+    fn word_end(&self) -> usize {
+        let mut index = self.cursor;
+        while index < self.line.len() && self.line[index].is_whitespace() {
+            index += 1;
+        }
+        while index < self.line.len() && !self.line[index].is_whitespace() {
+            index += 1;
+        }
+        index
+    }
+
+    /// Redraws the line after the prompt, relative to the position of the cursor after the previous redraw.
+    fn render(&mut self, history: &[String], output: &mut dyn Write) -> io::Result<()> {
+        let (text, cursor) = match &self.search {
+            Some(search) => {
+                let found = search.found.map_or("", |found| history[found].as_str());
+                let prefix = format!("{}`{}': ", SEARCH_PROMPT, search.query);
+                let offset = found
+                    .find(&search.query)
+                    .map_or(0, |index| found[..index].chars().count());
+                let cursor = prefix.chars().count() + offset;
+                (prefix + found, cursor)
+            }
+            None => (self.line.iter().collect(), self.cursor),
+        };
+        if self.drawn > 0 {
+            write!(output, "\x1b[{}D", self.drawn)?;
+        }
+        write!(output, "{}\x1b[K", text)?;
+        let back = text.chars().count() - cursor;
+        if back > 0 {
+            write!(output, "\x1b[{}D", back)?;
+        }
+        self.drawn = cursor;
+        output.flush()
+    }
+}
+
+#[cfg(unix)]
+struct RawMode {
+    original: libc::termios,
+}
+
+#[cfg(unix)]
+impl RawMode {
+    fn enable() -> io::Result<Self> {
+        let mut original = std::mem::MaybeUninit::<libc::termios>::uninit();
+        // SAFETY: tcgetattr initializes the given termios when it succeeds
+        let original = unsafe {
+            if libc::tcgetattr(libc::STDIN_FILENO, original.as_mut_ptr()) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            original.assume_init()
+        };
+        let mut raw = original;
+        raw.c_iflag &= !(libc::ICRNL | libc::IXON);
+        raw.c_lflag &= !(libc::ECHO | libc::ICANON | libc::IEXTEN | libc::ISIG);
+        raw.c_cc[libc::VMIN] = 1;
+        raw.c_cc[libc::VTIME] = 0;
+        // SAFETY: raw is a valid termios obtained from tcgetattr
+        if unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSADRAIN, &raw) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(RawMode { original })
+    }
+}
+
+#[cfg(unix)]
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        // SAFETY: original is the valid termios captured when raw mode was enabled
+        unsafe {
+            libc::tcsetattr(libc::STDIN_FILENO, libc::TCSADRAIN, &self.original);
+        }
+    }
+}
+
+/// Waits up to the given time for the standard input to become readable.
+#[cfg(unix)]
+fn input_ready(timeout: Duration) -> io::Result<bool> {
+    let mut input = libc::pollfd {
+        fd: libc::STDIN_FILENO,
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    loop {
+        // SAFETY: input is a valid pollfd for the duration of the call
+        match unsafe { libc::poll(&mut input, 1, timeout.as_millis() as libc::c_int) } {
+            -1 => {
+                let e = io::Error::last_os_error();
+                if e.kind() != io::ErrorKind::Interrupted {
+                    return Err(e);
+                }
+            }
+            ready => return Ok(ready > 0),
+        }
+    }
+}
+
+/// Reads the standard input without the buffering of [Stdin].
+#[cfg(unix)]
+fn read_stdin(buf: &mut [u8]) -> io::Result<usize> {
+    // SAFETY: buf is valid for writes of its length for the duration of the call
+    match unsafe { libc::read(libc::STDIN_FILENO, buf.as_mut_ptr().cast(), buf.len()) } {
+        -1 => Err(io::Error::last_os_error()),
+        len => Ok(len as usize),
+    }
+}
+
+#[cfg(not(unix))]
+fn input_ready(_timeout: Duration) -> io::Result<bool> {
+    Ok(true)
+}
+
+#[cfg(not(unix))]
+fn read_stdin(_buf: &mut [u8]) -> io::Result<usize> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "raw mode is not supported"))
+}
+
+#[cfg(not(unix))]
+struct RawMode;
+
+#[cfg(not(unix))]
+impl RawMode {
+    fn enable() -> io::Result<Self> {
+        Err(io::Error::new(io::ErrorKind::Unsupported, "raw mode is not supported"))
+    }
+}
@@ -0,0 +1,201 @@
+use std::collections::HashSet;
+use std::convert::TryFrom;
+use std::io;
+use std::io::{BufRead, Write};
+use std::vec::Vec;
+
+use crate::spec::{ArgSpec, ArgType, CommandSpec, OptionSpec, ParsedArgs};
+use crate::{CliError, CliHandler};
+
+/// The command that writes the lines of its input containing a pattern.
+pub const GREP: &str = "grep";
+/// The command that writes the first lines of its input.
+pub const HEAD: &str = "head";
+/// The command that writes the last lines of its input.
+pub const TAIL: &str = "tail";
+/// The command that writes the number of lines, words and bytes of its input.
+pub const WC: &str = "wc";
+/// The command that writes the lines of its input in order.
+pub const SORT: &str = "sort";
+/// The number of lines written by [HEAD] and [TAIL] unless another number is given.
+pub const DEFAULT_LINES: usize = 10;
+
+/// A handler providing filters that transform the output of the preceding command of a pipeline, e.g.
+/// `history | grep start | tail -n 3`:
+/// - `grep [-i] [-v] [-c] <pattern>` writes the lines containing the pattern, ignoring case with `-i`, the lines not
+///   containing the pattern with `-v`, or the number of such lines with `-c`
+/// - `head [-n <lines>]` writes the first [DEFAULT_LINES] or given number of lines
+/// - `tail [-n <lines>]` writes the last [DEFAULT_LINES] or given number of lines
+/// - `wc [-l] [-w] [-c]` writes the number of lines, words and bytes, or only those requested
+/// - `sort [-n] [-r] [-u]` writes the lines in lexicographic order, or in numeric order of their first words with
+///   `-n`, reversing the order with `-r` and omitting repeated lines with `-u`
+///
+/// The filters are only available once the handler is added to a manager. A filter that is not part of a pipeline
+/// reads no input.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Filters;
+
+impl CliHandler for Filters {
+    fn get_commands(&self) -> HashSet<&'static str> {
+        [GREP, HEAD, TAIL, WC, SORT].iter().cloned().collect()
+    }
+
+    fn get_name(&self) -> &str {
+        "Filters"
+    }
+
+    fn reads_input(&self, _command: &str) -> bool {
+        true
+    }
+
+    fn handle_input(
+        &self,
+        command: &str,
+        args: Vec<String>,
+        input: &mut dyn BufRead,
+        writer: &mut dyn Write,
+    ) -> Result<(), CliError> {
+        let args = match self.get_spec(command) {
+            Some(spec) => spec.parse(args)?,
+            None => return Err(CliError::ExecutionError(format!("Unknown command: {}", command))),
+        };
+        let mut text = String::new();
+        input.read_to_string(&mut text).map_err(execution_error)?;
+        let output = match command {
+            GREP => grep(&text, &args),
+            HEAD => {
+                let count = line_count(&args)?;
+                text.lines().take(count).map(str::to_owned).collect()
+            }
+            TAIL => {
+                let count = line_count(&args)?;
+                let lines: Vec<&str> = text.lines().collect();
+                lines[lines.len().saturating_sub(count)..]
+                    .iter()
+                    .map(|line| line.to_string())
+                    .collect()
+            }
+            WC => vec![word_count(&text, &args)],
+            SORT => sort(&text, &args),
+            _ => return Err(CliError::ExecutionError(format!("Unknown command: {}", command))),
+        };
+        for line in output {
+            writeln!(writer, "{}", line).map_err(execution_error)?;
+        }
+        Ok(())
+    }
+
+    fn handle_arguments(&self, command: &str, args: ParsedArgs, writer: &mut dyn Write) -> Result<(), CliError> {
+        self.handle_input(command, args.into_raw(), &mut io::empty(), writer)
+    }
+
+    fn get_spec(&self, command: &str) -> Option<CommandSpec> {
+        match command {
+            GREP => Some(
+                CommandSpec::new()
+                    .arg(ArgSpec::new("pattern", ArgType::String))
+                    .option(OptionSpec::flag("ignore-case").short('i'))
+                    .option(OptionSpec::flag("invert-match").short('v'))
+                    .option(OptionSpec::flag("count").short('c')),
+            ),
+            HEAD | TAIL => Some(CommandSpec::new().option(OptionSpec::value("lines", ArgType::Int).short('n'))),
+            WC => Some(
+                CommandSpec::new()
+                    .option(OptionSpec::flag("lines").short('l'))
+                    .option(OptionSpec::flag("words").short('w'))
+                    .option(OptionSpec::flag("bytes").short('c')),
+            ),
+            SORT => Some(
+                CommandSpec::new()
+                    .option(OptionSpec::flag("numeric").short('n'))
+                    .option(OptionSpec::flag("reverse").short('r'))
+                    .option(OptionSpec::flag("unique").short('u')),
+            ),
+            _ => None,
+        }
+    }
+
+    fn get_summary(&self, command: &str) -> Option<String> {
+        let summary = match command {
+            GREP => "Writes the lines of the input containing the given pattern.",
+            HEAD => "Writes the first lines of the input.",
+            TAIL => "Writes the last lines of the input.",
+            WC => "Writes the number of lines, words and bytes of the input.",
+            SORT => "Writes the lines of the input in order.",
+            _ => return None,
+        };
+        Some(String::from(summary))
+    }
+}
+
+fn grep(text: &str, args: &ParsedArgs) -> Vec<String> {
+    let ignore_case = args.has_option("ignore-case");
+    let fold = |text: &str| {
+        if ignore_case {
+            text.to_lowercase()
+        } else {
+            text.to_owned()
+        }
+    };
+    let pattern = fold(args.get_str("pattern").unwrap_or_default());
+    let invert = args.has_option("invert-match");
+    let lines: Vec<String> = text
+        .lines()
+        .filter(|line| fold(line).contains(&pattern) != invert)
+        .map(str::to_owned)
+        .collect();
+    if args.has_option("count") {
+        vec![lines.len().to_string()]
+    } else {
+        lines
+    }
+}
+
+fn line_count(args: &ParsedArgs) -> Result<usize, CliError> {
+    match args.get_int("lines") {
+        Some(count) => usize::try_from(count).map_err(|_| {
+            CliError::ArgumentParseFailure(format!("invalid value '{}' for --lines: must not be negative", count))
+        }),
+        None => Ok(DEFAULT_LINES),
+    }
+}
+
+fn word_count(text: &str, args: &ParsedArgs) -> String {
+    let counts = [
+        ("lines", text.matches('\n').count()),
+        ("words", text.split_whitespace().count()),
+        ("bytes", text.len()),
+    ];
+    let all = !counts.iter().any(|(name, _)| args.has_option(name));
+    let counts: Vec<String> = counts
+        .iter()
+        .filter(|(name, _)| all || args.has_option(name))
+        .map(|(_, count)| count.to_string())
+        .collect();
+    counts.join(" ")
+}
+
+fn sort(text: &str, args: &ParsedArgs) -> Vec<String> {
+    let mut lines: Vec<&str> = text.lines().collect();
+    if args.has_option("numeric") {
+        // lines that do not start with a number precede those that do
+        let key = |line: &str| line.split_whitespace().next().and_then(|word| word.parse::<f64>().ok());
+        lines.sort_by(|a, b| match (key(a), key(b)) {
+            (Some(x), Some(y)) => x.total_cmp(&y).then_with(|| a.cmp(b)),
+            (x, y) => x.is_some().cmp(&y.is_some()).then_with(|| a.cmp(b)),
+        });
+    } else {
+        lines.sort_unstable();
+    }
+    if args.has_option("unique") {
+        lines.dedup();
+    }
+    if args.has_option("reverse") {
+        lines.reverse();
+    }
+    lines.into_iter().map(str::to_owned).collect()
+}
+
+fn execution_error(e: io::Error) -> CliError {
+    CliError::ExecutionError(e.to_string())
+}
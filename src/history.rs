@@ -0,0 +1,168 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::vec::Vec;
+
+/// The default maximum number of entries retained in a [History].
+pub const DEFAULT_HISTORY_SIZE: usize = 1000;
+
+/// The prefix of a reference to an entry of the history, e.g. `!3` or `!!`.
+pub const HISTORY_PREFIX: char = '!';
+
+/// A bounded record of executed commands, optionally persisted to a file. Repeats of the most recent command are not
+/// recorded, and earlier commands keep their numbers until the oldest commands are discarded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct History {
+    entries: Vec<String>,
+    max_size: usize,
+    file: Option<PathBuf>,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        History::new(DEFAULT_HISTORY_SIZE)
+    }
+}
+
+impl History {
+    /// Constructs a new History that is kept in memory only.
+    ///
+    /// # Arguments
+    /// `max_size` - The maximum number of entries retained; the oldest entries are discarded first.
+    pub fn new(max_size: usize) -> Self {
+        History {
+            entries: Vec::new(),
+            max_size,
+            file: None,
+        }
+    }
+
+    /// Constructs a new History that is persisted to the given file, loading any entries it already contains.
+    /// The file is rewritten each time an entry is added.
+    ///
+    /// # Arguments
+    /// `path` - The path of the file, which is created when the first entry is added if it does not exist.
+    /// `max_size` - The maximum number of entries retained; the oldest entries are discarded first.
+    ///
+    /// # Errors
+    /// An io::Error if the file exists but could not be read.
+    pub fn with_file<P: Into<PathBuf>>(path: P, max_size: usize) -> io::Result<Self> {
+        let path = path.into();
+        let mut history = History::new(max_size);
+        match fs::read_to_string(&path) {
+            Ok(contents) => contents.lines().for_each(|line| history.push(line)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+        history.file = Some(path);
+        Ok(history)
+    }
+
+    /// Gets the recorded commands, from oldest to newest.
+    pub fn entries(&self) -> &[String] {
+        &self.entries
+    }
+
+    /// Gets the command with the given 1-based number, as displayed by the history command.
+    pub fn get(&self, number: usize) -> Option<&str> {
+        self.entries.get(number.checked_sub(1)?).map(String::as_str)
+    }
+
+    /// Gets the most recently recorded command.
+    pub fn last(&self) -> Option<&str> {
+        self.entries.last().map(String::as_str)
+    }
+
+    /// Gets the number of recorded commands.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Gets whether no commands have been recorded.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Gets the maximum number of entries retained.
+    pub fn max_size(&self) -> usize {
+        self.max_size
+    }
+
+    /// Gets the file to which the history is persisted, if any.
+    pub fn file(&self) -> Option<&Path> {
+        self.file.as_deref()
+    }
+
+    /// Records the given command, ignoring blank lines and repeats of the most recent command.
+    ///
+    /// # Arguments
+    /// `line` - The command to record.
+    ///
+    /// # Errors
+    /// An io::Error if the history could not be written to its file. The command is recorded in memory regardless.
+    pub fn add(&mut self, line: &str) -> io::Result<()> {
+        self.push(line);
+        self.save()
+    }
+
+    /// Removes all recorded commands.
+    ///
+    /// # Errors
+    /// An io::Error if the history could not be written to its file.
+    pub fn clear(&mut self) -> io::Result<()> {
+        self.entries.clear();
+        self.save()
+    }
+
+    fn push(&mut self, line: &str) {
+        if line.trim().is_empty() {
+            return;
+        }
+        if self.last() == Some(line) {
+            return;
+        }
+        self.entries.push(line.to_owned());
+        let excess = self.entries.len().saturating_sub(self.max_size);
+        self.entries.drain(..excess);
+    }
+
+    fn save(&self) -> io::Result<()> {
+        match &self.file {
+            Some(path) => {
+                let mut contents = self.entries.join("\n");
+                if !contents.is_empty() {
+                    contents.push('\n');
+                }
+                fs::write(path, contents)
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Replaces a reference to an entry at the start of the given line with that entry: `!!` refers to the most
+    /// recent entry, `!n` to the entry numbered n, and `!-n` to the nth most recent entry. Any text following the
+    /// reference is kept.
+    ///
+    /// # Returns
+    /// `None` if the line does not start with a reference, otherwise the expanded line, or the reference if it does
+    /// not refer to an entry.
+    pub(crate) fn expand(&self, line: &str) -> Option<Result<String, String>> {
+        let line = line.trim_start();
+        let end = line.find(char::is_whitespace).unwrap_or(line.len());
+        let (reference, rest) = line.split_at(end);
+        let target = reference.strip_prefix(HISTORY_PREFIX)?;
+        let entry = if target == HISTORY_PREFIX.to_string() {
+            self.last()
+        } else if let Some(offset) = target.strip_prefix('-') {
+            let offset: usize = offset.parse().ok()?;
+            self.len().checked_sub(offset).and_then(|index| self.get(index + 1))
+        } else {
+            self.get(target.parse().ok()?)
+        };
+        Some(
+            entry
+                .map(|entry| format!("{}{}", entry, rest))
+                .ok_or_else(|| reference.to_owned()),
+        )
+    }
+}
@@ -1,16 +1,64 @@
 #![deny(missing_docs)]
 //! A service used to provide a command line user interface.
+//!
+//! # Built-in commands
+//! The manager provides the commands listed in [manager::RESERVED_COMMANDS], which handlers cannot claim: adding a
+//! handler with one of these commands fails with [RegistrationError::Reserved]. Releases after 0.2.1 add the built-in
+//! commands `echo`, `alias`, `unalias`, `set`, `unset` and `vars`, so handlers that provide commands with these names
+//! must rename them before they can be added (see CHANGELOG.md).
 
+/// Defines the aliases expanded by the manager before a command is dispatched.
+pub mod alias;
+/// Defines the completion of partially entered command lines.
+pub mod completion;
+/// Defines a line editor used to read command line input from a terminal.
+pub mod editor;
+/// Defines filters that transform the output of the preceding command of a pipeline.
+pub mod filter;
+/// Defines the record of previously executed commands.
+pub mod history;
 /// Defines a manager responsible for handling command line input and output.
 pub mod manager;
+/// Defines the registry of the handlers responsible for each command.
+pub mod registry;
+/// Defines the options and results of executing scripts of commands.
+pub mod script;
+/// Defines declarative specifications of the arguments accepted by commands.
+pub mod spec;
+/// Defines a tokenizer used to split command line input into arguments.
+pub mod tokenizer;
+/// Defines the variables of a session, which the manager expands in command lines.
+pub mod variable;
 
 use std::collections::HashSet;
 use std::error::Error;
 use std::fmt;
 use std::fmt::{Display, Formatter};
-use std::io::Write;
+use std::io;
+use std::io::{BufRead, Write};
 use std::vec::Vec;
 
+use crate::spec::{CommandSpec, ParsedArgs};
+use crate::tokenizer::ParseError;
+
+/// Derives [CliCommands] for an enum whose variants are commands, or a struct that is a single command. Requires the
+/// `derive` feature.
+///
+/// Command names are the kebab-case names of the variants or struct, e.g. `IsRunning` becomes `is-running`, unless
+/// overridden with `#[cli(name = "...")]`. Alternate names are declared with `#[cli(alias = "...")]`, which may be
+/// repeated. Fields are the positional arguments of the command, in declaration order, and are converted with
+/// [std::str::FromStr]; `Option<T>` fields are optional and a trailing `Vec<T>` field is variadic. A `bool` field
+/// marked `#[cli(flag)]` is set when the flag is given, and an `Option<T>` or `Vec<T>` field marked `#[cli(option)]`
+/// holds the last or all values of an option taking a value. Options are named after their fields in kebab-case, and
+/// `#[cli(short = 'x')]` gives them a short name. The arguments are parsed with the [spec::CommandSpec] returned by
+/// [CliCommands::spec()], so options may be given anywhere and `--` ends them.
+/// The first paragraph of the doc comment is the summary of the command and the whole doc comment is its description.
+///
+/// Adding `#[cli(handler = Type)]` to the enum or struct also implements [CliHandler] for `Type`, which must
+/// implement [CliExecute] for the derived type.
+#[cfg(feature = "derive")]
+pub use jdn_cli_derive::CliCommands;
+
 /// An enumeration of errors that can occur while executing a CLI command.
 #[derive(Debug)]
 pub enum CliError {
@@ -29,6 +77,30 @@ pub enum CliError {
     /// Indicates an error occurred while executing the command.
     /// The internal attribute contains a description of the error that occurred.
     ExecutionError(String),
+    /// Indicates the command is not available.
+    InvalidCommand {
+        /// The command that was received.
+        command: String,
+        /// The available commands with names similar to the command that was received, most similar first.
+        suggestions: Vec<String>,
+    },
+    /// Indicates the command is an abbreviation of more than one available command.
+    AmbiguousCommand {
+        /// The command that was received.
+        command: String,
+        /// The available commands matching the command that was received, sorted by name.
+        candidates: Vec<String>,
+    },
+    /// Indicates the command line could not be split into a command and arguments.
+    /// The internal attribute contains the error reported by the tokenizer.
+    SyntaxError(ParseError),
+    /// Indicates a file to or from which a stream of the command is redirected could not be opened, read or written.
+    RedirectionError {
+        /// The path of the file.
+        path: String,
+        /// The underlying error.
+        error: io::Error,
+    },
 }
 
 impl Display for CliError {
@@ -51,22 +123,318 @@ impl Display for CliError {
             CliError::ExecutionError(s) => {
                 write!(f, "Execution error: {}", s)
             }
+            CliError::InvalidCommand { command, suggestions } => {
+                write!(f, "{} '{}'", manager::INVALID_COMMAND, command)?;
+                if !suggestions.is_empty() {
+                    write!(f, ". Did you mean {}?", quoted_list(suggestions))?;
+                }
+                Ok(())
+            }
+            CliError::AmbiguousCommand { command, candidates } => {
+                write!(
+                    f,
+                    "{} '{}': could be {}",
+                    manager::AMBIGUOUS_COMMAND,
+                    command,
+                    quoted_list(candidates)
+                )
+            }
+            CliError::SyntaxError(e) => {
+                write!(f, "Syntax error: {}", e)
+            }
+            CliError::RedirectionError { path, error } => {
+                write!(f, "Redirection error: {}: {}", path, error)
+            }
         }
     }
 }
 
-impl Error for CliError {}
+impl CliError {
+    /// Gets the process exit code representing the error:
+    /// - 1 for [CliError::ExecutionError]
+    /// - 2 for [CliError::InvalidNumberOfArguments]
+    /// - 3 for [CliError::ArgumentParseFailure]
+    /// - 4 for [CliError::SyntaxError]
+    /// - 5 for [CliError::RedirectionError]
+    /// - 127 for [CliError::InvalidCommand] and [CliError::AmbiguousCommand]
+    pub fn exit_code(&self) -> i32 {
+        match &self {
+            CliError::ExecutionError(_) => 1,
+            CliError::InvalidNumberOfArguments { .. } => 2,
+            CliError::ArgumentParseFailure(_) => 3,
+            CliError::SyntaxError(_) => 4,
+            CliError::RedirectionError { .. } => 5,
+            CliError::InvalidCommand { .. } | CliError::AmbiguousCommand { .. } => 127,
+        }
+    }
+}
+
+impl Error for CliError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match &self {
+            CliError::SyntaxError(e) => Some(e),
+            CliError::RedirectionError { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+/// Formats the given items as a quoted list, e.g. `'a', 'b' or 'c'`.
+fn quoted_list(items: &[String]) -> String {
+    let quoted: Vec<String> = items.iter().map(|item| format!("'{}'", item)).collect();
+    match quoted.split_last() {
+        Some((last, [])) => last.clone(),
+        Some((last, rest)) => format!("{} or {}", rest.join(", "), last),
+        None => String::new(),
+    }
+}
+
+/// An enumeration of errors that can occur while the manager is reading input or writing output.
+#[derive(Debug)]
+pub enum CliIoError {
+    /// Indicates an error occurred while reading from the input stream.
+    /// The internal attribute contains the underlying error.
+    Read(io::Error),
+    /// Indicates an error occurred while writing to the output stream.
+    /// The internal attribute contains the underlying error.
+    Write(io::Error),
+}
+
+impl Display for CliIoError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match &self {
+            CliIoError::Read(e) => write!(f, "Failed to read input: {}", e),
+            CliIoError::Write(e) => write!(f, "Failed to write output: {}", e),
+        }
+    }
+}
+
+impl Error for CliIoError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match &self {
+            CliIoError::Read(e) | CliIoError::Write(e) => Some(e),
+        }
+    }
+}
+
+/// An enumeration of the reasons a handler could not be added to the manager.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RegistrationError {
+    /// Indicates a command of the handler is already handled by another handler.
+    Conflict {
+        /// The command claimed by both handlers.
+        command: String,
+        /// The name of the handler already responsible for the command.
+        existing: String,
+        /// The name of the handler that could not be added.
+        handler: String,
+    },
+    /// Indicates a command of the handler is one of the built-in commands of the manager.
+    Reserved {
+        /// The reserved command.
+        command: String,
+        /// The name of the handler that could not be added.
+        handler: String,
+    },
+}
+
+impl Display for RegistrationError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match &self {
+            RegistrationError::Conflict {
+                command,
+                existing,
+                handler,
+            } => write!(
+                f,
+                "Command '{}' of {} is already handled by {}",
+                command, handler, existing
+            ),
+            RegistrationError::Reserved { command, handler } => {
+                write!(f, "Command '{}' of {} is reserved by the manager", command, handler)
+            }
+        }
+    }
+}
+
+impl Error for RegistrationError {}
 
 /// The trait that allows commands received from the command line interface to be translated and executed.
-pub trait CliHandler {
+/// Handlers are shared with any thread holding the [registry::CliRegistry] of a manager.
+pub trait CliHandler: Send + Sync {
     /// Gets the commands that the handler is able to translate and execute. Returns the commands for which the handler is responsible.
-    /// Note that the contents of the Set must not change over the lifetime of the handler.
+    /// A command may consist of several words separated by [registry::COMMAND_SEPARATOR], e.g. `db backup create`,
+    /// in which case it is a subcommand of the namespaces formed by its leading words (`db` and `db backup`).
+    /// Note that the contents of the Set must not change over the lifetime of the handler, unless
+    /// [CliHandler::has_dynamic_commands()] returns true or the handler is refreshed with
+    /// [registry::CliRegistry::refresh_handler()].
     fn get_commands(&self) -> HashSet<&'static str>;
 
+    /// Gets whether the commands of the handler are derived from runtime state, e.g. discovered devices or loaded
+    /// plugins, in which case [CliHandler::get_commands()] is called each time a command is looked up rather than
+    /// once when the handler is added. Returns false by default.
+    fn has_dynamic_commands(&self) -> bool {
+        false
+    }
+
+    /// Gets the alternate names of the given command, e.g. `calc` for `calculate`, which are expanded to the command
+    /// before it is dispatched and displayed alongside the command by the help command. An alias may consist of
+    /// several words, like a subcommand. Aliases that are reserved or that name an available command are ignored.
+    /// Returns an empty Vec by default.
+    fn get_aliases(&self, _command: &str) -> Vec<&'static str> {
+        Vec::new()
+    }
+
+    /// Gets the name of the handler, used to identify it in a [RegistrationError]. Returns the type name by default.
+    fn get_name(&self) -> &str {
+        std::any::type_name::<Self>()
+    }
+
     /// Parses the given arguments and executes the given command.
     /// Standard output generated by executing the command may be sent to the given writer.
+    /// Handlers must implement either this function or [CliHandler::handle_arguments()].
+    ///
+    /// # Errors
+    /// A Result indicating if an error occurred while executing the command, or if the command could not be executed.
+    fn handle_command(&self, command: &str, args: Vec<String>, writer: &mut dyn Write) -> Result<(), CliError> {
+        let _ = (args, writer);
+        Err(CliError::ExecutionError(format!("{} is not implemented", command)))
+    }
+
+    /// Gets whether the given command reads its input, in which case it is passed to [CliHandler::handle_input()]
+    /// when the command is part of a pipeline or its input is redirected from a file. The lines of a file redirected
+    /// to the input of a command that does not read it are appended to its arguments instead. Returns false by
+    /// default.
+    fn reads_input(&self, _command: &str) -> bool {
+        false
+    }
+
+    /// Executes the given command of a pipeline, e.g. `grep` in `list | grep name`, with the output of the preceding
+    /// command, or the file to which its input is redirected, as its input. Standard output generated by executing
+    /// the command may be sent to the given writer, and is the input of the next command of the pipeline, if any.
+    /// Only called for commands for which [CliHandler::reads_input()] returns true, unless the command is part of a
+    /// pipeline. By default, the input is ignored and the command is executed as if it were not part of a pipeline.
     ///
     /// # Errors
     /// A Result indicating if an error occurred while executing the command, or if the command could not be executed.
-    fn handle_command(&self, command: &str, args: Vec<String>, writer: &mut dyn Write) -> Result<(), CliError>;
+    fn handle_input(
+        &self,
+        command: &str,
+        args: Vec<String>,
+        input: &mut dyn BufRead,
+        writer: &mut dyn Write,
+    ) -> Result<(), CliError> {
+        let _ = input;
+        match self.get_spec(command) {
+            Some(spec) => self.handle_arguments(command, spec.parse(args)?, writer),
+            None => self.handle_command(command, args, writer),
+        }
+    }
+
+    /// Gets the specification of the arguments accepted by the given command. When a specification is returned, the
+    /// arguments are validated and converted before [CliHandler::handle_arguments()] is called.
+    /// Returns `None` by default, in which case [CliHandler::handle_command()] is called with the raw arguments.
+    fn get_spec(&self, _command: &str) -> Option<CommandSpec> {
+        None
+    }
+
+    /// Executes the given command with arguments that have been validated against [CliHandler::get_spec()].
+    /// Standard output generated by executing the command may be sent to the given writer.
+    /// By default, the raw arguments are passed to [CliHandler::handle_command()].
+    ///
+    /// # Errors
+    /// A Result indicating if an error occurred while executing the command, or if the command could not be executed.
+    fn handle_arguments(&self, command: &str, args: ParsedArgs, writer: &mut dyn Write) -> Result<(), CliError> {
+        self.handle_command(command, args.into_raw(), writer)
+    }
+
+    /// Gets a one-line summary of the given command, displayed alongside the command name by the help command.
+    /// Returns `None` by default.
+    fn get_summary(&self, _command: &str) -> Option<String> {
+        None
+    }
+
+    /// Gets a detailed description of the given command, displayed when help is requested for the command.
+    /// Returns `None` by default, in which case the summary is displayed instead.
+    fn get_description(&self, _command: &str) -> Option<String> {
+        None
+    }
+
+    /// Gets the usage line of the given command, e.g. `calculate <name> <num1> <operator> <num2>`.
+    /// Returns `None` by default, in which case the usage is generated from [CliHandler::get_spec()], or the command
+    /// name is displayed if there is no specification.
+    fn get_usage(&self, _command: &str) -> Option<String> {
+        None
+    }
+
+    /// Gets the candidate completions of an argument of the given command, e.g. known IDs or file paths (see
+    /// [completion::complete_path()]). Candidates that do not start with the partial argument are discarded.
+    /// Returns an empty Vec by default, in which case only the options of [CliHandler::get_spec()] are completed.
+    ///
+    /// # Arguments
+    /// `command` - The command whose argument is being completed.
+    /// `args` - The complete arguments preceding the argument being completed.
+    /// `partial` - The unquoted text of the argument being completed, which may be empty.
+    fn complete_argument(&self, _command: &str, _args: &[String], _partial: &str) -> Vec<String> {
+        Vec::new()
+    }
+}
+
+/// The trait implemented by types that represent a set of commands, usually derived with `#[derive(CliCommands)]`.
+pub trait CliCommands: Sized {
+    /// Gets the commands represented by the type.
+    fn commands() -> HashSet<&'static str>;
+
+    /// Converts the given command and arguments into a value of the type.
+    ///
+    /// # Errors
+    /// [CliError::InvalidNumberOfArguments] or [CliError::ArgumentParseFailure] if the arguments do not match the
+    /// command, or [CliError::ExecutionError] if the command is not represented by the type.
+    fn parse(command: &str, args: Vec<String>) -> Result<Self, CliError>;
+
+    /// Gets the specification of the arguments accepted by the given command, with which the arguments are parsed
+    /// before [CliCommands::from_args()] is called. Returns `None` by default.
+    fn spec(_command: &str) -> Option<CommandSpec> {
+        None
+    }
+
+    /// Converts the given command and arguments parsed according to [CliCommands::spec()] into a value of the type.
+    /// By default, the raw arguments are passed to [CliCommands::parse()].
+    ///
+    /// # Errors
+    /// [CliError::ArgumentParseFailure] if the arguments cannot be converted, or [CliError::ExecutionError] if the
+    /// command is not represented by the type.
+    fn from_args(command: &str, args: ParsedArgs) -> Result<Self, CliError> {
+        Self::parse(command, args.into_raw())
+    }
+
+    /// Gets a one-line summary of the given command. Returns `None` by default.
+    fn summary(_command: &str) -> Option<String> {
+        None
+    }
+
+    /// Gets a detailed description of the given command. Returns `None` by default.
+    fn description(_command: &str) -> Option<String> {
+        None
+    }
+
+    /// Gets the usage line of the given command. Returns `None` by default.
+    fn usage(_command: &str) -> Option<String> {
+        None
+    }
+
+    /// Gets the alternate names of the given command. Returns an empty Vec by default.
+    fn aliases(_command: &str) -> Vec<&'static str> {
+        Vec::new()
+    }
+}
+
+/// The trait that allows commands parsed into a [CliCommands] type to be executed.
+pub trait CliExecute<C: CliCommands> {
+    /// Executes the given command.
+    /// Standard output generated by executing the command may be sent to the given writer.
+    ///
+    /// # Errors
+    /// A Result indicating if an error occurred while executing the command.
+    fn execute(&self, command: C, writer: &mut dyn Write) -> Result<(), CliError>;
 }
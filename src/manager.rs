@@ -1,9 +1,26 @@
-use std::collections::HashMap;
-use std::io::{BufRead, BufReader, Stdin, Stdout, Write};
+use std::cmp;
+use std::collections::{BTreeMap, HashSet};
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::io::{BufRead, BufReader, BufWriter, Cursor, Read, Stdin, Stdout, Write};
+use std::mem;
+use std::path::Path;
 use std::sync::Arc;
 use std::vec::Vec;
 
-use crate::CliHandler;
+use crate::alias::{Aliases, ALIAS_SEPARATOR};
+use crate::completion;
+use crate::completion::{Completions, COMPLETE_COMMAND};
+use crate::editor::LineEditor;
+use crate::history::History;
+use crate::registry::{is_reserved, CliRegistry, HandlerId, COMMAND_SEPARATOR};
+use crate::script::{ScriptError, ScriptOptions, ScriptSummary, COMMENT};
+use crate::spec::{LONG_OPTION_PREFIX, SHORT_OPTION_PREFIX};
+use crate::tokenizer;
+use crate::tokenizer::{Command, Operator, Pipeline, Redirection, Word, WordPart};
+use crate::variable;
+use crate::variable::Variables;
+use crate::{CliError, CliHandler, CliIoError, RegistrationError};
 
 /// The string used to represent the manager is waiting for input.
 pub const PROMPT: &str = "> ";
@@ -11,23 +28,234 @@ pub const PROMPT: &str = "> ";
 pub const HELP: &str = "help";
 /// The command used to stop the manager.
 pub const EXIT: &str = "exit";
+/// The command used to list the previously executed commands.
+pub const HISTORY: &str = "history";
+/// The command used to display text, e.g. after another command fails.
+pub const ECHO: &str = "echo";
+/// The command used to define and list aliases.
+pub const ALIAS: &str = "alias";
+/// The command used to remove aliases.
+pub const UNALIAS: &str = "unalias";
+/// The command used to set variables.
+pub const SET: &str = "set";
+/// The command used to remove variables.
+pub const UNSET: &str = "unset";
+/// The command used to list variables.
+pub const VARS: &str = "vars";
+/// The commands built into the manager, which handlers cannot claim.
+pub const RESERVED_COMMANDS: [&str; 10] = [
+    HELP,
+    EXIT,
+    HISTORY,
+    ECHO,
+    ALIAS,
+    UNALIAS,
+    SET,
+    UNSET,
+    VARS,
+    COMPLETE_COMMAND,
+];
+/// The default maximum edit distance between an unknown command and the commands suggested in its place.
+pub const DEFAULT_SUGGESTION_DISTANCE: usize = 2;
 /// The message displayed when an invalid command is received by the manager.
 pub const INVALID_COMMAND: &str = "Invalid command";
+/// The message displayed when a command abbreviates more than one available command.
+pub const AMBIGUOUS_COMMAND: &str = "Ambiguous command";
+/// The process exit code reported when a command succeeds.
+pub const EXIT_SUCCESS: i32 = 0;
+
+const HELP_USAGE: &str = "help [command...]";
+const HELP_DESCRIPTION: &str = "Displays a summary of the available commands, or the usage and subcommands of the \
+                                given command.";
+const SUBCOMMANDS: &str = "Subcommands";
+const MAX_SUGGESTIONS: usize = 3;
+const EXIT_DESCRIPTION: &str = "Stops the command line interface.";
+const HISTORY_USAGE: &str = "history [count]";
+const HISTORY_DESCRIPTION: &str = "Displays the previously executed commands, or the given number of the most recent \
+                                   commands. Use !n to execute command n again, !-n to execute the nth most recent \
+                                   command again, or !! to execute the most recent command again.";
+const EVENT_NOT_FOUND: &str = "event not found";
+const ECHO_USAGE: &str = "echo [text...]";
+const ECHO_DESCRIPTION: &str = "Displays the given text.";
+const ALIASES: &str = "Aliases";
+const ALIAS_USAGE: &str = "alias [name[=command...]]";
+const ALIAS_DESCRIPTION: &str = "Defines an alias that stands for the given command line, or displays the given alias \
+                                 or all aliases.";
+const ALIAS_DEFINITION: &str = "<name>=<command>";
+const UNALIAS_USAGE: &str = "unalias <name...>";
+const UNALIAS_DESCRIPTION: &str = "Removes the given aliases.";
+const ALIAS_NOT_FOUND: &str = "alias not found";
+const ALIAS_RESERVED: &str = "reserved by the manager";
+const SET_USAGE: &str = "set <name> [value...]";
+const SET_DESCRIPTION: &str = "Sets the given variable to the given value, which replaces $name or ${name} in later \
+                               commands.";
+const VARIABLE_NAME: &str = "<name>";
+const UNSET_USAGE: &str = "unset <name...>";
+const UNSET_DESCRIPTION: &str = "Removes the given variables.";
+const VARS_DESCRIPTION: &str = "Displays all variables and their values.";
+const VARIABLE_NOT_FOUND: &str = "variable not found";
+const UNDEFINED_VARIABLE: &str = "undefined variable";
+
+/// An enumeration of the reasons the manager stopped processing input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitReason {
+    /// The user submitted a request to stop the manager ([EXIT]).
+    Exit,
+    /// The input stream reached its end.
+    EndOfInput,
+}
+
+/// An enumeration of the actions the manager may take when the input stream reaches its end.
+#[derive(Default)]
+pub enum EofPolicy {
+    /// Stops the manager, reporting [ExitReason::EndOfInput]. This is the default policy.
+    #[default]
+    Exit,
+    /// Stops the manager as if the [EXIT] command was received, reporting [ExitReason::Exit].
+    ExitCommand,
+    /// Invokes the given callback. The callback returns `true` if the manager should read the input again, e.g. once
+    /// more input has been supplied to the reader, or `false` if the manager should stop, reporting
+    /// [ExitReason::EndOfInput]. If the input is still at its end when read again, the manager stops without invoking
+    /// the callback again.
+    Callback(Box<dyn FnMut() -> bool + Send>),
+}
+
+/// The rules by which the manager matches the words of a command line to the available commands, including the
+/// built-in commands.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DispatchPolicy {
+    /// Whether commands must be entered with the same case as their names. Defaults to `false`, in which case a
+    /// command whose name matches exactly is preferred over commands whose names differ only in case.
+    pub case_sensitive: bool,
+    /// Whether a command may be abbreviated to any prefix that no other command shares, e.g. `calc` for `calculate`.
+    /// Each word of a subcommand may be abbreviated, e.g. `db back cr` for `db backup create`. Defaults to `false`.
+    pub allow_prefix: bool,
+}
+
+impl DispatchPolicy {
+    /// Gets the candidates matched by the given word, which are the candidate equal to the word if there is one,
+    /// otherwise the candidates equal to the word apart from case if the policy is not case-sensitive, otherwise the
+    /// candidates starting with the word if the policy allows prefixes.
+    fn matches(&self, word: &str, candidates: &[String]) -> Vec<String> {
+        if candidates.iter().any(|candidate| candidate == word) {
+            return vec![word.to_owned()];
+        }
+        let fold = |text: &str| {
+            if self.case_sensitive {
+                text.to_owned()
+            } else {
+                text.to_lowercase()
+            }
+        };
+        let word = fold(word);
+        let mut matches: Vec<String> = candidates
+            .iter()
+            .filter(|candidate| fold(candidate) == word)
+            .cloned()
+            .collect();
+        if matches.is_empty() && self.allow_prefix {
+            matches = candidates
+                .iter()
+                .filter(|candidate| fold(candidate).starts_with(&word))
+                .cloned()
+                .collect();
+        }
+        matches
+    }
+}
+
+/// Gets the text of the given word, unless it contains references or substitutions to be expanded.
+fn literal_text(word: &Word) -> Option<String> {
+    word.iter()
+        .map(|part| match part {
+            WordPart::Text(text) => Some(text.as_str()),
+            WordPart::Variable(_) | WordPart::Command(_) => None,
+        })
+        .collect()
+}
+
+/// Gets the stream to which the errors that do not end a command line are written: the given error stream, or the
+/// output stream if there is none.
+fn error_sink<'a>(errors: &'a mut Option<&mut dyn Write>, writer: &'a mut dyn Write) -> &'a mut dyn Write {
+    match errors {
+        Some(errors) => &mut **errors,
+        None => writer,
+    }
+}
+
+/// Reborrows the given error stream, if any, so that it can be passed on more than once.
+fn reborrow<'a>(errors: &'a mut Option<&mut dyn Write>) -> Option<&'a mut dyn Write> {
+    match errors {
+        Some(errors) => Some(&mut **errors),
+        None => None,
+    }
+}
+
+/// A command of a pipeline once the references and substitutions in its words and the paths of its redirections are
+/// expanded.
+struct ExpandedCommand<'a> {
+    words: Vec<String>,
+    redirections: Vec<(&'a Redirection, String)>,
+}
+
+enum Action {
+    Continue(Result<(), CliError>),
+    /// The command failed and its error has already been written to the file to which it was redirected.
+    Reported(CliError),
+    Exit,
+}
+
+impl Action {
+    fn succeeded(&self) -> bool {
+        matches!(self, Action::Continue(Ok(())))
+    }
+}
+
+/// The outcome of a command executed with [CliManager::execute()].
+#[derive(Debug)]
+pub struct CommandOutcome {
+    output: String,
+    result: Result<(), CliError>,
+    exit: bool,
+}
+
+impl CommandOutcome {
+    /// Gets the output written while executing the command, excluding the error message if the command failed.
+    pub fn output(&self) -> &str {
+        &self.output
+    }
+
+    /// Gets the result of executing the command.
+    pub fn result(&self) -> &Result<(), CliError> {
+        &self.result
+    }
+
+    /// Consumes the outcome, returning the result of executing the command.
+    pub fn into_result(self) -> Result<(), CliError> {
+        self.result
+    }
+
+    /// Gets whether the command was a request to stop the manager ([EXIT]).
+    pub fn is_exit(&self) -> bool {
+        self.exit
+    }
+}
+
+/// Reads a line entered at the prompt from the reader of a manager, which may complete words with the dispatcher.
+type ReadLine<R> = fn(&mut R, &mut String, &Dispatcher) -> io::Result<usize>;
 
 /// A manager responsible for handling command line input and output.
 pub struct CliManager<R: BufRead, W: Write> {
     reader: R,
     writer: W,
-    handlers: HashMap<String, Arc<dyn CliHandler>>,
+    dispatcher: Dispatcher,
+    eof_policy: EofPolicy,
+    read_line: ReadLine<R>,
 }
 
 impl Default for CliManager<BufReader<Stdin>, Stdout> {
     fn default() -> Self {
-        CliManager {
-            reader: BufReader::new(std::io::stdin()),
-            writer: std::io::stdout(),
-            handlers: HashMap::default(),
-        }
+        CliManager::with_reader_writer(BufReader::new(std::io::stdin()), std::io::stdout())
     }
 }
 
@@ -38,88 +266,1321 @@ impl CliManager<BufReader<Stdin>, Stdout> {
     }
 }
 
+impl CliManager<LineEditor<Stdin, Stdout>, Stdout> {
+    /// Constructs a new CliManager that reads input with a [LineEditor], allowing the user to edit each command and
+    /// recall previous commands when the standard streams are terminals.
+    pub fn with_line_editor() -> Self {
+        CliManager::with_editor_writer(LineEditor::stdio(), std::io::stdout())
+    }
+}
+
+impl<I: Read, O: Write, W: 'static + Write> CliManager<LineEditor<I, O>, W> {
+    /// Constructs a new CliManager that reads input with the given [LineEditor], which recalls the commands in
+    /// [CliManager::history()] in place of its own history and completes the word before the cursor with
+    /// [CliManager::complete()] when Tab is pressed.
+    pub fn with_editor_writer(editor: LineEditor<I, O>, writer: W) -> Self {
+        let mut manager = CliManager::with_reader_writer(editor, writer);
+        manager.read_line = |editor, buf, dispatcher| {
+            let history = dispatcher.history.entries();
+            editor.read_line_with(buf, history, &mut |line: &str| dispatcher.complete_line(line))
+        };
+        manager
+    }
+}
+
 impl<R: BufRead, W: 'static + Write> CliManager<R, W> {
     /// Constructs a new CliManager with the given read/write streams.
     pub fn with_reader_writer(reader: R, writer: W) -> CliManager<R, W> {
         CliManager {
             reader,
             writer,
-            handlers: HashMap::new(),
+            dispatcher: Dispatcher::default(),
+            eof_policy: EofPolicy::default(),
+            read_line: |reader, buf, _| reader.read_line(buf),
         }
     }
 
     /// Starts the command line interface. Note that this is a blocking operation; once this function returns, the
-    /// user has submitted a request to stop the application ([EXIT]).
-    pub fn start(&mut self) {
+    /// user has submitted a request to stop the application ([EXIT]) or the input stream has reached its end and the
+    /// [EofPolicy] has requested to stop.
+    ///
+    /// # Errors
+    /// A [CliIoError] if the input stream could not be read or the output stream could not be written.
+    pub fn start(&mut self) -> Result<ExitReason, CliIoError> {
+        let mut retrying = false;
         loop {
-            write!(self.writer, "{}", PROMPT).expect("Failed to print prompt");
-            self.writer.flush().expect("Failed to flush prompt");
+            write!(self.writer, "{}", PROMPT).map_err(CliIoError::Write)?;
+            self.writer.flush().map_err(CliIoError::Write)?;
             let mut input = String::new();
-            self.reader.read_line(&mut input).expect("Failed to read line");
-            let input = input.trim().to_owned();
-            if input.is_empty() {
-                continue;
+            if (self.read_line)(&mut self.reader, &mut input, &self.dispatcher).map_err(CliIoError::Read)? == 0 {
+                match &mut self.eof_policy {
+                    EofPolicy::Exit => return Ok(ExitReason::EndOfInput),
+                    EofPolicy::ExitCommand => return Ok(ExitReason::Exit),
+                    EofPolicy::Callback(callback) => {
+                        // reading an input that remains at its end would otherwise never stop
+                        if !retrying && callback() {
+                            retrying = true;
+                            continue;
+                        }
+                        return Ok(ExitReason::EndOfInput);
+                    }
+                }
             }
-            let (command, args) = parse_input(input);
-            if command.is_empty() {
-                writeln!(self.writer, "{}", INVALID_COMMAND).expect("Failed to print `Invalid command`");
-                continue;
+            retrying = false;
+            let input = input.trim_end_matches(['\n', '\r']);
+            match self
+                .dispatcher
+                .run_interactive(input, &mut self.writer)
+                .map_err(CliIoError::Write)?
+            {
+                Action::Exit => return Ok(ExitReason::Exit),
+                Action::Continue(Err(e)) => writeln!(self.writer, "{}", e).map_err(CliIoError::Write)?,
+                Action::Continue(Ok(())) | Action::Reported(_) => {}
             }
+        }
+    }
 
-            if EXIT.eq_ignore_ascii_case(&command) {
+    /// Executes each line of the given script as if it had been entered at the prompt, until the script ends or the
+    /// [EXIT] command is executed. Blank lines and lines starting with [COMMENT] are ignored. The error of each
+    /// failed command is written to the output stream along with its line number, unless it is redirected to a file.
+    ///
+    /// # Arguments
+    /// `script` - The stream from which to read the script.
+    /// `options` - The options controlling how the script is executed.
+    ///
+    /// # Errors
+    /// A [CliIoError] if the script could not be read or the output stream could not be written.
+    pub fn run_script<S: BufRead>(&mut self, script: S, options: ScriptOptions) -> Result<ScriptSummary, CliIoError> {
+        let mut summary = ScriptSummary::default();
+        for (index, line) in script.lines().enumerate() {
+            let line = line.map_err(CliIoError::Read)?;
+            let command = line.trim();
+            if command.is_empty() || command.starts_with(COMMENT) {
+                continue;
+            }
+            if options.show_prompt {
+                write!(self.writer, "{}", PROMPT).map_err(CliIoError::Write)?;
+            }
+            if options.echo {
+                writeln!(self.writer, "{}", command).map_err(CliIoError::Write)?;
+            }
+            summary.executed += 1;
+            let (error, reported) = match self
+                .dispatcher
+                .run_line(command, &mut self.writer, None)
+                .map_err(CliIoError::Write)?
+            {
+                Action::Exit => {
+                    summary.exited = true;
+                    break;
+                }
+                Action::Continue(Ok(())) => continue,
+                Action::Continue(Err(e)) => (e, false),
+                Action::Reported(e) => (e, true),
+            };
+            let failure = ScriptError::new(index + 1, command.to_owned(), error);
+            if !reported {
+                writeln!(self.writer, "{}", failure).map_err(CliIoError::Write)?;
+            }
+            summary.failures.push(failure);
+            if options.stop_on_error {
                 break;
-            } else if HELP.eq_ignore_ascii_case(&command) {
-                let mut cmds: Vec<String> = self.handlers.iter().map(|(cmd, _)| cmd.clone()).collect();
-                cmds.sort();
-                for cmd in cmds {
-                    writeln!(self.writer, "{}", cmd).expect("Failed to print help output");
+            }
+        }
+        self.writer.flush().map_err(CliIoError::Write)?;
+        Ok(summary)
+    }
+
+    /// Executes the script at the given path. See [CliManager::run_script()].
+    ///
+    /// # Errors
+    /// A [CliIoError] if the script could not be opened or read, or the output stream could not be written.
+    pub fn run_script_file<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        options: ScriptOptions,
+    ) -> Result<ScriptSummary, CliIoError> {
+        let file = File::open(path).map_err(CliIoError::Read)?;
+        self.run_script(BufReader::new(file), options)
+    }
+
+    /// Executes a single command given as process arguments, e.g. `std::env::args()`, without writing a prompt. The
+    /// first argument is taken to be the program name and is ignored, the second is the command, and the remaining
+    /// arguments are passed to the handler as-is. If no command is given, the help summary is written and the command
+    /// fails with [CliError::InvalidNumberOfArguments].
+    ///
+    /// # Returns
+    /// The process exit code representing the outcome: [EXIT_SUCCESS] if the command succeeded, or
+    /// [CliError::exit_code()] if it failed, in which case the error is also written to the output stream.
+    ///
+    /// # Errors
+    /// A [CliIoError] if the output stream could not be written.
+    pub fn run_args<I, S>(&mut self, args: I) -> Result<i32, CliIoError>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let mut args = args.into_iter().skip(1).map(Into::into);
+        let action = match args.next() {
+            Some(command) if command == COMPLETE_COMMAND => {
+                let mut words: Vec<String> = args.collect();
+                let partial = words.pop().unwrap_or_default();
+                for candidate in self.dispatcher.complete(&words, &partial) {
+                    writeln!(self.writer, "{}", candidate).map_err(CliIoError::Write)?;
                 }
-            } else {
-                let handler = self.handlers.get(&command);
-                match handler {
-                    Some(ref value) => {
-                        if let Err(msg) = value.handle_command(&command, args, &mut self.writer) {
-                            writeln!(self.writer, "{}", msg).expect("Failed to print error message");
-                        }
-                    }
-                    None => writeln!(self.writer, "{}", INVALID_COMMAND).expect("Failed to print `Invalid command`"),
-                };
+                Ok(Action::Continue(Ok(())))
+            }
+            Some(command) => {
+                let words = std::iter::once(command).chain(args).collect::<Vec<String>>();
+                let line = vec![(Operator::Sequence, vec![Command::from(words)])];
+                self.dispatcher.run_pipelines(line, &mut self.writer, None)
             }
+            None => self.dispatcher.print_help(&[], &mut self.writer).map(|_| {
+                Action::Continue(Err(CliError::InvalidNumberOfArguments {
+                    min: 1,
+                    max: None,
+                    given: 0,
+                }))
+            }),
+        };
+        let code = match action.map_err(CliIoError::Write)? {
+            Action::Continue(Err(e)) => {
+                writeln!(self.writer, "{}", e).map_err(CliIoError::Write)?;
+                e.exit_code()
+            }
+            Action::Reported(e) => e.exit_code(),
+            Action::Continue(Ok(())) | Action::Exit => EXIT_SUCCESS,
+        };
+        self.writer.flush().map_err(CliIoError::Write)?;
+        Ok(code)
+    }
+
+    /// Executes the given line as if it had been entered at the prompt, capturing the output rather than writing it
+    /// to the output stream.
+    ///
+    /// # Arguments
+    /// `line` - The command and its arguments.
+    pub fn execute(&mut self, line: &str) -> CommandOutcome {
+        let mut output = Vec::new();
+        let action = self
+            .dispatcher
+            .run_interactive(line, &mut output)
+            .unwrap_or_else(|e| Action::Continue(Err(CliError::ExecutionError(e.to_string()))));
+        let (result, exit) = match action {
+            Action::Continue(result) => (result, false),
+            Action::Reported(e) => (Err(e), false),
+            Action::Exit => (Ok(()), true),
+        };
+        CommandOutcome {
+            output: String::from_utf8_lossy(&output).into_owned(),
+            result,
+            exit,
         }
     }
 
+    /// Completes the last word of the given command line. The first word is completed from the names of the
+    /// available commands, the argument of the help command is completed from the names of the available commands,
+    /// the path following a redirection is completed from the file system, a word starting with
+    /// [tokenizer::VARIABLE] and the arguments of the unset command are completed from the names of the variables,
+    /// and any other argument is completed by [CliHandler::complete_argument()] and the options of
+    /// [CliHandler::get_spec()].
+    ///
+    /// # Arguments
+    /// `line` - The command line, ending with the partial word to complete, which may be empty.
+    pub fn complete(&self, line: &str) -> Completions {
+        self.dispatcher.complete_line(line)
+    }
+
+    /// Gets the record of the commands entered at the prompt or passed to [CliManager::execute()].
+    pub fn history(&self) -> &History {
+        &self.dispatcher.history
+    }
+
+    /// Sets the record of the commands entered at the prompt or passed to [CliManager::execute()], e.g. to persist
+    /// the history to a file with [History::with_file()].
+    ///
+    /// # Arguments
+    /// `history` - The history to record commands into.
+    pub fn set_history(&mut self, history: History) {
+        self.dispatcher.history = history;
+    }
+
+    /// Gets the aliases defined by the user with the alias command.
+    pub fn aliases(&self) -> &Aliases {
+        &self.dispatcher.aliases
+    }
+
+    /// Sets the aliases defined by the user, e.g. to persist the aliases to a file with [Aliases::with_file()].
+    /// Aliases with the names of built-in commands are ignored.
+    ///
+    /// # Arguments
+    /// `aliases` - The aliases to expand and to define new aliases into.
+    pub fn set_aliases(&mut self, aliases: Aliases) {
+        self.dispatcher.aliases = aliases;
+    }
+
+    /// Gets the variables set with the set command.
+    pub fn variables(&self) -> &Variables {
+        &self.dispatcher.variables
+    }
+
+    /// Sets the variables expanded in command lines, e.g. to seed them from the environment with
+    /// [Variables::from_env()].
+    ///
+    /// # Arguments
+    /// `variables` - The variables to expand and to set new variables into.
+    pub fn set_variables(&mut self, variables: Variables) {
+        self.dispatcher.variables = variables;
+    }
+
+    /// Gets the available commands with names similar to the given command, most similar first, as suggested when the
+    /// command is not available.
+    ///
+    /// # Arguments
+    /// `command` - The command, which may be a subcommand, e.g. `db backp`.
+    pub fn suggest(&self, command: &str) -> Vec<String> {
+        self.dispatcher.suggest(command)
+    }
+
+    /// Sets the maximum edit distance between an unknown command and the available commands suggested in its place.
+    ///
+    /// # Arguments
+    /// `distance` - The maximum number of edits, which defaults to [DEFAULT_SUGGESTION_DISTANCE]. Zero disables
+    /// suggestions.
+    pub fn set_suggestion_distance(&mut self, distance: usize) {
+        self.dispatcher.suggestion_distance = distance;
+    }
+
+    /// Gets the rules by which commands are matched to the available commands.
+    pub fn dispatch_policy(&self) -> DispatchPolicy {
+        self.dispatcher.policy
+    }
+
+    /// Sets the rules by which commands are matched to the available commands.
+    ///
+    /// # Arguments
+    /// `policy` - The policy to apply to subsequent commands.
+    pub fn set_dispatch_policy(&mut self, policy: DispatchPolicy) {
+        self.dispatcher.policy = policy;
+    }
+
+    /// Sets the action taken when the input stream reaches its end.
+    ///
+    /// # Arguments
+    /// `policy` - The policy to apply to subsequent ends of input.
+    pub fn set_eof_policy(&mut self, policy: EofPolicy) {
+        self.eof_policy = policy;
+    }
+
+    /// Gets a shared handle to the handlers of the manager, which can be used to add or remove handlers from
+    /// another thread while the manager is running.
+    pub fn registry(&self) -> CliRegistry {
+        self.dispatcher.registry.clone()
+    }
+
     /// Adds the given CliHandler. All commands returned by [CliHandler::get_commands()] will now be forwarded to
     /// this handler.
     ///
     /// # Arguments
     /// `handler` - A reference to the handler to add. This reference is cloned for retention by the manager.
-    pub fn add_handler(&mut self, handler: Arc<dyn CliHandler>) {
-        for cmd in handler.get_commands() {
-            self.handlers.insert(cmd.to_string(), Arc::clone(&handler));
-        }
-    }
-}
-
-fn parse_input(input: String) -> (String, Vec<String>) {
-    let quoted_vars: Vec<&str> = input.split('\"').collect();
-    let mut command = String::new();
-    let mut vars: Vec<String> = Vec::new();
-    for element in quoted_vars.iter().enumerate() {
-        if element.0 % 2 == 0 {
-            let space_vars: Vec<&str> = element.1.split(' ').collect();
-            for var in space_vars {
-                let var = var.trim();
-                if !var.is_empty() {
-                    if command.is_empty() {
-                        command.push_str(var);
-                    } else {
-                        vars.push(var.to_string());
+    ///
+    /// # Returns
+    /// The identifier of the handler, which can be passed to [CliManager::remove_handler_id()].
+    ///
+    /// # Errors
+    /// A [RegistrationError] if any command of the handler is in [RESERVED_COMMANDS] or is already handled by another
+    /// handler, in which case none of the commands are added.
+    pub fn add_handler(&mut self, handler: Arc<dyn CliHandler>) -> Result<HandlerId, RegistrationError> {
+        self.dispatcher.registry.add_handler(handler)
+    }
+
+    /// Adds the given CliHandler, replacing any handler already responsible for one of its commands. All commands
+    /// returned by [CliHandler::get_commands()] will now be forwarded to this handler.
+    ///
+    /// # Arguments
+    /// `handler` - A reference to the handler to add. This reference is cloned for retention by the manager.
+    ///
+    /// # Returns
+    /// The identifier of the handler, which can be passed to [CliManager::remove_handler_id()].
+    ///
+    /// # Errors
+    /// A [RegistrationError] if any command of the handler is in [RESERVED_COMMANDS], in which case none of the
+    /// commands are added.
+    pub fn replace_handler(&mut self, handler: Arc<dyn CliHandler>) -> Result<HandlerId, RegistrationError> {
+        self.dispatcher.registry.replace_handler(handler)
+    }
+
+    /// Removes the given handler and all commands forwarded to it.
+    ///
+    /// # Arguments
+    /// `handler` - A reference to the handler to remove, compared by identity.
+    ///
+    /// # Returns
+    /// True if the handler was removed, even if it was responsible for no commands.
+    pub fn remove_handler(&mut self, handler: &Arc<dyn CliHandler>) -> bool {
+        self.dispatcher.registry.remove_handler(handler)
+    }
+
+    /// Removes the handler with the given identifier and all commands forwarded to it.
+    ///
+    /// # Arguments
+    /// `id` - The identifier returned when the handler was added.
+    ///
+    /// # Returns
+    /// True if the handler was removed, even if it was responsible for no commands.
+    pub fn remove_handler_id(&mut self, id: HandlerId) -> bool {
+        self.dispatcher.registry.remove_handler_id(id)
+    }
+
+    /// Removes the given command, leaving the other commands of its handler available.
+    ///
+    /// # Arguments
+    /// `command` - The command to remove.
+    ///
+    /// # Returns
+    /// True if the command was removed.
+    pub fn remove_command(&mut self, command: &str) -> bool {
+        self.dispatcher.registry.remove_command(command)
+    }
+}
+
+struct Dispatcher {
+    registry: CliRegistry,
+    history: History,
+    aliases: Aliases,
+    variables: Variables,
+    suggestion_distance: usize,
+    policy: DispatchPolicy,
+}
+
+impl Default for Dispatcher {
+    fn default() -> Self {
+        Dispatcher {
+            registry: CliRegistry::default(),
+            history: History::default(),
+            aliases: Aliases::default(),
+            variables: Variables::default(),
+            suggestion_distance: DEFAULT_SUGGESTION_DISTANCE,
+            policy: DispatchPolicy::default(),
+        }
+    }
+}
+
+impl Dispatcher {
+    fn run_interactive(&mut self, input: &str, writer: &mut dyn Write) -> io::Result<Action> {
+        let line = match self.history.expand(input) {
+            Some(Ok(line)) => {
+                writeln!(writer, "{}", line)?;
+                line
+            }
+            Some(Err(reference)) => {
+                let message = format!("{}: {}", reference, EVENT_NOT_FOUND);
+                return Ok(Action::Continue(Err(CliError::ExecutionError(message))));
+            }
+            None => input.to_owned(),
+        };
+        if let Err(e) = self.history.add(&line) {
+            writeln!(writer, "Failed to save history: {}", e)?;
+        }
+        self.run_line(&line, writer, None)
+    }
+
+    /// Runs each pipeline of the given line in turn, according to the operators separating them. The error of a
+    /// failed pipeline is written before the next pipeline runs; the result of the last pipeline that ran is returned.
+    ///
+    /// # Arguments
+    /// `input` - The line to run.
+    /// `writer` - The stream to which the output of the line is written.
+    /// `errors` - The stream to which the errors that do not end the line are written, if not the output stream.
+    fn run_line(&mut self, input: &str, writer: &mut dyn Write, errors: Option<&mut dyn Write>) -> io::Result<Action> {
+        match tokenizer::split_commands(input) {
+            Ok(pipelines) => self.run_pipelines(pipelines, writer, errors),
+            Err(e) => Ok(Action::Continue(Err(CliError::SyntaxError(e)))),
+        }
+    }
+
+    /// Runs each of the given pipelines in turn, according to the operators preceding them, once the aliases defined
+    /// by the user are expanded (see [Dispatcher::expand_user_aliases()]).
+    ///
+    /// # Arguments
+    /// `pipelines` - The pipelines of the line to run.
+    /// `writer` - The stream to which the output of the line is written.
+    /// `errors` - The stream to which the errors that do not end the line are written, if not the output stream.
+    fn run_pipelines(
+        &mut self,
+        pipelines: Vec<(Operator, Pipeline)>,
+        writer: &mut dyn Write,
+        mut errors: Option<&mut dyn Write>,
+    ) -> io::Result<Action> {
+        let pipelines = match self.expand_user_aliases(pipelines, &HashSet::new()) {
+            Ok(pipelines) => pipelines,
+            Err(e) => return Ok(Action::Continue(Err(e))),
+        };
+        let mut status = Action::Continue(Ok(()));
+        for (operator, pipeline) in pipelines {
+            let run = match operator {
+                Operator::Sequence => true,
+                Operator::And => status.succeeded(),
+                Operator::Or => !status.succeeded(),
+            };
+            if !run {
+                continue;
+            }
+            if let Action::Continue(Err(e)) = mem::replace(&mut status, Action::Continue(Ok(()))) {
+                writeln!(error_sink(&mut errors, writer), "{}", e)?;
+            }
+            match self.run_pipeline(pipeline, writer, reborrow(&mut errors))? {
+                Action::Exit => return Ok(Action::Exit),
+                action => status = action,
+            }
+        }
+        Ok(status)
+    }
+
+    /// Runs each command of the given pipeline in turn, passing the output of each command to the next as its input.
+    /// The error of a failed command is written before the next command runs; the result of the last command is
+    /// returned.
+    ///
+    /// # Arguments
+    /// `pipeline` - The pipeline to run.
+    /// `writer` - The stream to which the output of the last command is written.
+    /// `errors` - The stream to which the errors that do not end the pipeline are written, if not the output stream.
+    fn run_pipeline(
+        &mut self,
+        pipeline: Pipeline,
+        writer: &mut dyn Write,
+        mut errors: Option<&mut dyn Write>,
+    ) -> io::Result<Action> {
+        let count = pipeline.len();
+        let mut input: Option<Cursor<Vec<u8>>> = None;
+        for (index, command) in pipeline.iter().enumerate() {
+            let last = index + 1 == count;
+            let expanded = self.expand_command(command, error_sink(&mut errors, writer))?;
+            let mut output = Vec::new();
+            let action = match expanded {
+                Ok(command) => {
+                    let target: &mut dyn Write = if last { &mut *writer } else { &mut output };
+                    let source = input.as_mut().map(|input| input as &mut dyn BufRead);
+                    self.run_redirected(command, source, target)?
+                }
+                Err(e) => Action::Continue(Err(e)),
+            };
+            match action {
+                Action::Exit => return Ok(Action::Exit),
+                action if last => return Ok(action),
+                Action::Continue(Err(e)) => writeln!(error_sink(&mut errors, writer), "{}", e)?,
+                Action::Continue(Ok(())) | Action::Reported(_) => {}
+            }
+            input = Some(Cursor::new(output));
+        }
+        Ok(Action::Continue(Ok(())))
+    }
+
+    /// Expands the references and substitutions in the words of the given command and the paths of its redirections.
+    ///
+    /// # Arguments
+    /// `command` - The command to expand.
+    /// `errors` - The stream to which the errors that do not end the command line of a substitution are written.
+    fn expand_command<'a>(
+        &mut self,
+        command: &'a Command,
+        errors: &mut dyn Write,
+    ) -> io::Result<Result<ExpandedCommand<'a>, CliError>> {
+        let mut words = Vec::new();
+        for word in command.words() {
+            match self.expand_word(word, errors)? {
+                Ok(word) => words.push(word),
+                Err(e) => return Ok(Err(e)),
+            }
+        }
+        let mut redirections = Vec::new();
+        for redirection in command.redirections() {
+            match self.expand_word(redirection.path(), errors)? {
+                Ok(path) => redirections.push((redirection, path)),
+                Err(e) => return Ok(Err(e)),
+            }
+        }
+        Ok(Ok(ExpandedCommand { words, redirections }))
+    }
+
+    /// Runs the given command, reading its input from and writing its output and error to the files to which they
+    /// are redirected, if any, rather than the given input and writer. The lines of the file to which the input of a
+    /// command that does not read its input is redirected are appended to its arguments. Each file is opened before
+    /// the command runs, in the order the redirections appear; when a stream is redirected more than once, the last
+    /// file is used.
+    fn run_redirected(
+        &mut self,
+        command: ExpandedCommand,
+        input: Option<&mut dyn BufRead>,
+        writer: &mut dyn Write,
+    ) -> io::Result<Action> {
+        let ExpandedCommand {
+            mut words,
+            redirections,
+        } = command;
+        let mut file_input = None;
+        let mut output = None;
+        let mut error = None;
+        for (redirection, path) in redirections {
+            let opened = match redirection {
+                Redirection::Input(_) => {
+                    File::open(&path).map(|file| file_input = Some((path.clone(), BufReader::new(file))))
+                }
+                Redirection::Output { append, .. } => {
+                    open_redirection(&path, *append).map(|file| output = Some((path.clone(), BufWriter::new(file))))
+                }
+                Redirection::Error { append, .. } => {
+                    open_redirection(&path, *append).map(|file| error = Some((path.clone(), file)))
+                }
+            };
+            if let Err(e) = opened {
+                return Ok(Action::Continue(Err(redirection_error(&path, e))));
+            }
+        }
+        let mut file_input = match file_input {
+            Some((path, file)) if !self.reads_input(&words) => {
+                for line in file.lines() {
+                    match line {
+                        Ok(line) => words.push(line),
+                        Err(e) => return Ok(Action::Continue(Err(redirection_error(&path, e)))),
+                    }
+                }
+                None
+            }
+            file_input => file_input,
+        };
+        let input = match file_input.as_mut() {
+            Some((_, file)) => Some(file as &mut dyn BufRead),
+            None => input.map(|input| input as &mut dyn BufRead),
+        };
+        let action = match output.as_mut() {
+            Some((path, file)) => self
+                .run_command(words, input, file)
+                .and_then(|action| file.flush().map(|_| action))
+                .unwrap_or_else(|e| Action::Continue(Err(redirection_error(path, e)))),
+            None => self.run_command(words, input, writer)?,
+        };
+        match (action, error) {
+            (Action::Continue(Err(e)), Some((path, mut file))) => match writeln!(file, "{}", e) {
+                Ok(()) => Ok(Action::Reported(e)),
+                Err(write_error) => Ok(Action::Continue(Err(redirection_error(&path, write_error)))),
+            },
+            (action, _) => Ok(action),
+        }
+    }
+
+    /// Replaces each reference in the given word with the value of its variable, and each substitution with the
+    /// output of its command line, which runs like a line entered at the prompt. Only the output of the command line
+    /// is substituted; the errors of its commands that do not end it are written to the given error stream. Running
+    /// [EXIT] within a substitution only ends the substitution.
+    ///
+    /// # Returns
+    /// The expanded word, a [CliError::ExecutionError] if a referenced variable is not set, or the error of the
+    /// command line of a substitution that failed.
+    fn expand_word(&mut self, word: &Word, errors: &mut dyn Write) -> io::Result<Result<String, CliError>> {
+        let mut text = String::new();
+        for part in word {
+            match part {
+                WordPart::Text(part) => text.push_str(part),
+                WordPart::Variable(name) => match self.variables.get(name) {
+                    Some(value) => text.push_str(value),
+                    None => {
+                        let message = format!("{}: {}", name, UNDEFINED_VARIABLE);
+                        return Ok(Err(CliError::ExecutionError(message)));
+                    }
+                },
+                WordPart::Command(line) => {
+                    let mut output = Vec::new();
+                    match self.run_line(line, &mut output, Some(errors))? {
+                        Action::Continue(Err(e)) | Action::Reported(e) => return Ok(Err(e)),
+                        Action::Continue(Ok(())) | Action::Exit => {
+                            text.push_str(String::from_utf8_lossy(&output).trim())
+                        }
                     }
                 }
             }
+        }
+        Ok(Ok(text))
+    }
+
+    /// Runs the command given by the words, passing the given input to its handler if it is part of a pipeline.
+    fn run_command(
+        &mut self,
+        words: Vec<String>,
+        input: Option<&mut dyn BufRead>,
+        writer: &mut dyn Write,
+    ) -> io::Result<Action> {
+        let mut words = match self.expand(words) {
+            Ok(words) => words,
+            Err(e) => return Ok(Action::Continue(Err(e))),
+        };
+        match words.first().map(String::as_str) {
+            None => return Ok(Action::Continue(Ok(()))),
+            Some(EXIT) => return Ok(Action::Exit),
+            Some(HELP) => return self.print_help(&words[1..], writer).map(Action::Continue),
+            Some(HISTORY) => return self.print_history(&words[1..], writer).map(Action::Continue),
+            Some(ECHO) => return writeln!(writer, "{}", words[1..].join(" ")).map(|_| Action::Continue(Ok(()))),
+            Some(ALIAS) => return self.run_alias(&words[1..], writer).map(Action::Continue),
+            Some(UNALIAS) => return Ok(Action::Continue(self.run_unalias(&words[1..]))),
+            Some(SET) => return Ok(Action::Continue(self.run_set(&words[1..]))),
+            Some(UNSET) => return Ok(Action::Continue(self.run_unset(&words[1..]))),
+            Some(VARS) => return self.print_variables(&words[1..], writer).map(Action::Continue),
+            Some(_) => {}
+        }
+        let result = match self.registry.resolve(&words) {
+            Some((command, handler, len)) => {
+                let args = words.split_off(len);
+                match input {
+                    Some(input) => handler.handle_input(&command, args, input, writer),
+                    None => dispatch(handler.as_ref(), &command, args, writer),
+                }
+            }
+            None => {
+                let command = self.unknown_command(words);
+                let suggestions = self.suggest(&command);
+                Err(CliError::InvalidCommand { command, suggestions })
+            }
+        };
+        Ok(Action::Continue(result))
+    }
+
+    /// Gets whether the command given by the words reads its input (see [CliHandler::reads_input()]). Built-in
+    /// commands never do.
+    fn reads_input(&self, words: &[String]) -> bool {
+        self.expand(words.to_vec())
+            .ok()
+            .and_then(|words| self.registry.resolve(&words))
+            .is_some_and(|(command, handler, _)| handler.reads_input(&command))
+    }
+
+    /// Replaces the leading words of the given words with the available command or alias they match according to the
+    /// dispatch policy, then expands any alias declared by a handler.
+    fn expand(&self, words: Vec<String>) -> Result<Vec<String>, CliError> {
+        let words = self.canonicalize(words)?;
+        let words = self.expand_handler_aliases(words);
+        self.canonicalize(words)
+    }
+
+    /// Replaces the leading words that name an alias declared by a handler with the words of the command it stands
+    /// for, repeatedly, expanding each alias at most once.
+    fn expand_handler_aliases(&self, mut words: Vec<String>) -> Vec<String> {
+        let handler_aliases = self.handler_aliases();
+        let mut expanded = HashSet::new();
+        loop {
+            let alias = (1..=words.len()).rev().find_map(|len| {
+                let name = words[..len].join(&COMMAND_SEPARATOR.to_string());
+                if expanded.contains(&name) {
+                    return None;
+                }
+                handler_aliases.get(&name).map(|command| (name, len, command))
+            });
+            let (name, len, command) = match alias {
+                Some(alias) => alias,
+                None => return words,
+            };
+            let mut replacement: Vec<String> = command.split(COMMAND_SEPARATOR).map(str::to_owned).collect();
+            replacement.extend(words.split_off(len));
+            words = replacement;
+            expanded.insert(name);
+        }
+    }
+
+    /// Replaces each command of the given pipelines whose leading words name an alias defined by the user with the
+    /// command line the alias stands for, as if it had been entered in their place: the remaining words and the
+    /// redirections of the command are appended to the last command of the alias, and the operators and pipes of the
+    /// alias split the surrounding pipeline, so `rs && echo done` for the alias `rs='stop; start'` runs
+    /// `stop; start && echo done`. The commands of an alias are expanded in turn, except for the given aliases being
+    /// expanded, so an alias may refer to a command with the same name. Aliases defined by the user take precedence
+    /// over commands.
+    ///
+    /// # Errors
+    /// A [CliError::SyntaxError] if the command line of an alias could not be parsed.
+    fn expand_user_aliases(
+        &self,
+        pipelines: Vec<(Operator, Pipeline)>,
+        expanding: &HashSet<String>,
+    ) -> Result<Vec<(Operator, Pipeline)>, CliError> {
+        let mut line = Vec::new();
+        for (operator, pipeline) in pipelines {
+            let mut current = (operator, Pipeline::new());
+            for command in pipeline {
+                let (name, len, alias) = match self.find_user_alias(&command, expanding) {
+                    Some(alias) => alias,
+                    None => {
+                        current.1.push(command);
+                        continue;
+                    }
+                };
+                let mut alias = tokenizer::split_commands(alias).map_err(CliError::SyntaxError)?;
+                let rest = Command::new(command.words()[len..].to_vec(), command.redirections().to_vec());
+                match alias.last_mut().and_then(|(_, pipeline)| pipeline.last_mut()) {
+                    Some(last) => last.append(rest),
+                    None => alias.push((Operator::Sequence, vec![rest])),
+                }
+                let mut expanding = expanding.clone();
+                expanding.insert(name);
+                let mut alias = self.expand_user_aliases(alias, &expanding)?.into_iter();
+                if let Some((_, pipeline)) = alias.next() {
+                    current.1.extend(pipeline);
+                }
+                for next in alias {
+                    line.push(mem::replace(&mut current, next));
+                }
+            }
+            line.push(current);
+        }
+        Ok(line)
+    }
+
+    /// Finds the alias defined by the user that is named by the leading literal words of the given command, matched
+    /// according to the dispatch policy, unless it is one of the given aliases being expanded.
+    ///
+    /// # Returns
+    /// The name of the alias, the number of words of the command consumed by its name and the command line it stands
+    /// for.
+    fn find_user_alias(&self, command: &Command, expanding: &HashSet<String>) -> Option<(String, usize, &str)> {
+        let words: Vec<String> = command.words().iter().map_while(literal_text).collect();
+        // an ambiguous command is reported when it runs
+        let words = self.canonicalize(words).ok()?;
+        (1..=words.len()).rev().find_map(|len| {
+            let name = words[..len].join(&COMMAND_SEPARATOR.to_string());
+            let alias = self.user_alias(&name).filter(|_| !expanding.contains(&name))?;
+            Some((name, len, alias))
+        })
+    }
+
+    /// Gets the command line the given alias defined by the user stands for, unless the alias is reserved.
+    fn user_alias(&self, name: &str) -> Option<&str> {
+        self.aliases.get(name).filter(|_| !is_reserved(name))
+    }
+
+    /// Gets the aliases declared by handlers and the commands they stand for, ignoring aliases that are reserved or
+    /// name an available command.
+    fn handler_aliases(&self) -> BTreeMap<String, String> {
+        let mut aliases = BTreeMap::new();
+        for (command, handler) in self.registry.handlers() {
+            for alias in handler.get_aliases(&command) {
+                if !is_reserved(alias) && self.registry.get(alias).is_none() {
+                    aliases.entry(alias.to_owned()).or_insert_with(|| command.clone());
+                }
+            }
+        }
+        aliases
+    }
+
+    /// Gets the aliases that stand for each available command alone, sorted by name.
+    fn command_aliases(&self) -> BTreeMap<String, Vec<String>> {
+        let mut aliases: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        for (alias, command) in self.handler_aliases() {
+            aliases.entry(command).or_default().push(alias);
+        }
+        for (alias, command) in self.aliases.entries() {
+            let command = tokenizer::tokenize(command)
+                .unwrap_or_default()
+                .join(&COMMAND_SEPARATOR.to_string());
+            if !is_reserved(alias) && *alias != command && self.registry.get(&command).is_some() {
+                aliases.entry(command).or_default().push(alias.clone());
+            }
+        }
+        for names in aliases.values_mut() {
+            names.sort();
+            names.dedup();
+        }
+        aliases
+    }
+
+    /// Replaces each leading word that matches an available command or namespace according to the dispatch policy with
+    /// the word it matches, e.g. `db backup` for `DB back`. Replacement stops at the first word that matches nothing,
+    /// or that matches more than one word following a command.
+    ///
+    /// # Errors
+    /// A [CliError::AmbiguousCommand] if a word that does not follow a command matches more than one word.
+    fn canonicalize(&self, mut words: Vec<String>) -> Result<Vec<String>, CliError> {
+        for index in 0..words.len() {
+            let namespace = &words[..index];
+            let mut matches = self.policy.matches(&words[index], &self.next_words(namespace));
+            match matches.len() {
+                0 => break,
+                1 => words[index] = matches.remove(0),
+                _ if index > 0
+                    && self
+                        .registry
+                        .get(&namespace.join(&COMMAND_SEPARATOR.to_string()))
+                        .is_some() =>
+                {
+                    break
+                }
+                _ => {
+                    let separator = COMMAND_SEPARATOR.to_string();
+                    let prefix: String = namespace.iter().map(|word| format!("{}{}", word, separator)).collect();
+                    return Err(CliError::AmbiguousCommand {
+                        command: format!("{}{}", prefix, words[index]),
+                        candidates: matches.iter().map(|word| format!("{}{}", prefix, word)).collect(),
+                    });
+                }
+            }
+        }
+        Ok(words)
+    }
+
+    /// Gets the name of the unknown command given by the words: the first word, or the deepest namespace followed by
+    /// the word that is not one of its subcommands.
+    fn unknown_command(&self, mut words: Vec<String>) -> String {
+        let len = (1..=words.len())
+            .rev()
+            .find(|len| !self.registry.subcommands(&words[..*len]).is_empty())
+            .map_or(1, |len| cmp::min(len + 1, words.len()));
+        words.truncate(len);
+        words.join(&COMMAND_SEPARATOR.to_string())
+    }
+
+    fn complete_line(&self, line: &str) -> Completions {
+        let (words, partial, start) = completion::split_line(line);
+        if tokenizer::ends_with_redirection(&line[..start]) {
+            return Completions::new(start, completion::complete_path(&partial));
+        }
+        Completions::new(start, self.complete(&words, &partial))
+    }
+
+    fn complete(&self, words: &[String], partial: &str) -> Vec<String> {
+        if let Some(prefix) = partial.strip_prefix(tokenizer::VARIABLE) {
+            return self
+                .variables
+                .entries()
+                .keys()
+                .filter(|name| name.starts_with(prefix))
+                .map(|name| format!("{}{}", tokenizer::VARIABLE, name))
+                .collect();
+        }
+        let line = vec![(Operator::Sequence, vec![Command::from(words.to_vec())])];
+        // the words being completed continue the last command of any alias defined by the user
+        let words = match self.expand_user_aliases(line, &HashSet::new()) {
+            Ok(mut line) => line
+                .pop()
+                .and_then(|(_, mut pipeline)| pipeline.pop())
+                .map(|command| {
+                    command
+                        .words()
+                        .iter()
+                        .map(|word| literal_text(word).unwrap_or_default())
+                        .collect()
+                })
+                .unwrap_or_default(),
+            Err(_) => words.to_vec(),
+        };
+        let words = self.expand(words.clone()).unwrap_or(words);
+        let path = match words.split_first() {
+            Some((command, path)) if command == HELP => {
+                self.canonicalize(path.to_vec()).unwrap_or_else(|_| path.to_vec())
+            }
+            _ => words.clone(),
+        };
+        let mut candidates = self.next_words(&path);
+        if words.first().map(String::as_str) == Some(UNSET) {
+            candidates.extend(self.variables.entries().keys().cloned());
+        }
+        if let Some((command, handler, len)) = self.registry.resolve(&words) {
+            candidates.extend(handler.complete_argument(&command, &words[len..], partial));
+            if let Some(spec) = handler
+                .get_spec(&command)
+                .filter(|_| partial.starts_with(SHORT_OPTION_PREFIX))
+            {
+                let options = spec.options().iter();
+                candidates.extend(options.map(|option| format!("{}{}", LONG_OPTION_PREFIX, option.name())));
+            }
+        }
+        if self.policy.case_sensitive {
+            candidates.retain(|candidate| candidate.starts_with(partial));
         } else {
-            vars.push(element.1.to_string());
+            let partial = partial.to_lowercase();
+            candidates.retain(|candidate| candidate.to_lowercase().starts_with(&partial));
+        }
+        candidates.sort();
+        candidates.dedup();
+        candidates
+    }
+
+    /// Gets the words that may follow the given namespace, including the built-in commands if it is empty.
+    fn next_words<S: AsRef<str>>(&self, namespace: &[S]) -> Vec<String> {
+        let aliases = self
+            .aliases
+            .entries()
+            .keys()
+            .filter(|alias| !is_reserved(alias))
+            .cloned()
+            .chain(self.handler_aliases().into_keys());
+        let mut words: Vec<String> = self
+            .registry
+            .commands()
+            .into_iter()
+            .chain(aliases)
+            .filter_map(|name| {
+                let mut words = name.split(COMMAND_SEPARATOR);
+                let within = namespace.iter().all(|word| words.next() == Some(word.as_ref()));
+                words.next().filter(|_| within).map(str::to_owned)
+            })
+            .collect();
+        if namespace.is_empty() {
+            let builtins = [HELP, EXIT, HISTORY, ECHO, ALIAS, UNALIAS, SET, UNSET, VARS];
+            words.extend(builtins.iter().map(|command| command.to_string()));
+        }
+        words.sort();
+        words.dedup();
+        words
+    }
+
+    fn suggest(&self, command: &str) -> Vec<String> {
+        let words: Vec<&str> = command.split(COMMAND_SEPARATOR).collect();
+        let (word, namespace) = match words.split_last() {
+            Some(split) if self.suggestion_distance > 0 => split,
+            _ => return Vec::new(),
+        };
+        let folded = word.to_lowercase();
+        let mut suggestions: Vec<(usize, String)> = self
+            .next_words(namespace)
+            .into_iter()
+            .filter(|candidate| candidate != word)
+            .map(|candidate| (edit_distance(&folded, &candidate.to_lowercase()), candidate))
+            .filter(|(distance, _)| *distance <= self.suggestion_distance)
+            .collect();
+        suggestions.sort();
+        suggestions
+            .into_iter()
+            .take(MAX_SUGGESTIONS)
+            .map(|(_, candidate)| {
+                let mut words = namespace.to_vec();
+                words.push(&candidate);
+                words.join(&COMMAND_SEPARATOR.to_string())
+            })
+            .collect()
+    }
+
+    fn print_help(&self, args: &[String], writer: &mut dyn Write) -> io::Result<Result<(), CliError>> {
+        let args = match self.canonicalize(args.to_vec()) {
+            Ok(args) => args,
+            Err(e) => return Ok(Err(e)),
+        };
+        let name = args.join(&COMMAND_SEPARATOR.to_string());
+        if let Some(command) = self.user_alias(&name) {
+            writeln!(writer, "{} is an alias for '{}'", name, command)?;
+            return Ok(Ok(()));
+        }
+        let args = match self.handler_aliases().remove(&name) {
+            Some(command) => command.split(COMMAND_SEPARATOR).map(str::to_owned).collect(),
+            None => args,
+        };
+        let args = args.as_slice();
+        if let [command] = args {
+            let builtin = match command.as_str() {
+                HELP => Some((HELP_USAGE, HELP_DESCRIPTION)),
+                EXIT => Some((EXIT, EXIT_DESCRIPTION)),
+                HISTORY => Some((HISTORY_USAGE, HISTORY_DESCRIPTION)),
+                ECHO => Some((ECHO_USAGE, ECHO_DESCRIPTION)),
+                ALIAS => Some((ALIAS_USAGE, ALIAS_DESCRIPTION)),
+                UNALIAS => Some((UNALIAS_USAGE, UNALIAS_DESCRIPTION)),
+                SET => Some((SET_USAGE, SET_DESCRIPTION)),
+                UNSET => Some((UNSET_USAGE, UNSET_DESCRIPTION)),
+                VARS => Some((VARS, VARS_DESCRIPTION)),
+                _ => None,
+            };
+            if let Some((usage, description)) = builtin {
+                writeln!(writer, "Usage: {}", usage)?;
+                writeln!(writer, "{}", description)?;
+                return Ok(Ok(()));
+            }
+        }
+        let command = self.registry.resolve(args).filter(|(_, _, len)| *len == args.len());
+        let entries = self.subcommand_entries(args);
+        match &command {
+            Some((command, handler, _)) => {
+                let usage = handler
+                    .get_usage(command)
+                    .or_else(|| handler.get_spec(command).map(|spec| spec.usage(command)))
+                    .unwrap_or_else(|| command.clone());
+                writeln!(writer, "Usage: {}", usage)?;
+                if let Some(description) = handler
+                    .get_description(command)
+                    .or_else(|| handler.get_summary(command))
+                {
+                    writeln!(writer, "{}", description)?;
+                }
+                if let Some(aliases) = self.command_aliases().get(command) {
+                    writeln!(writer, "{}: {}", ALIASES, aliases.join(", "))?;
+                }
+                if !entries.is_empty() {
+                    writeln!(writer, "{}:", SUBCOMMANDS)?;
+                }
+            }
+            None if !args.is_empty() && entries.is_empty() => {
+                let command = args.join(&COMMAND_SEPARATOR.to_string());
+                let suggestions = self.suggest(&command);
+                return Ok(Err(CliError::InvalidCommand { command, suggestions }));
+            }
+            None => {}
+        }
+        let width = entries
+            .iter()
+            .map(|(cmd, _)| cmd.chars().count())
+            .max()
+            .unwrap_or_default();
+        for (cmd, summary) in entries {
+            match summary {
+                Some(summary) => writeln!(writer, "{:width$}  {}", cmd, summary, width = width)?,
+                None => writeln!(writer, "{}", cmd)?,
+            }
+        }
+        Ok(Ok(()))
+    }
+
+    /// Gets the commands and namespaces directly within the given namespace, along with their summaries. The summary
+    /// of a namespace that is not itself a command lists its subcommands.
+    fn subcommand_entries(&self, namespace: &[String]) -> Vec<(String, Option<String>)> {
+        let mut children: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        for command in self.registry.subcommands(namespace) {
+            let words: Vec<&str> = command.split(COMMAND_SEPARATOR).collect();
+            let nested = children
+                .entry(words[..=namespace.len()].join(&COMMAND_SEPARATOR.to_string()))
+                .or_default();
+            if let Some(word) = words.get(namespace.len() + 1) {
+                if !nested.iter().any(|nested| nested == word) {
+                    nested.push(word.to_string());
+                }
+            }
+        }
+        let aliases = self.command_aliases();
+        children
+            .into_iter()
+            .map(|(child, nested)| {
+                let summary = match self.registry.get(&child) {
+                    Some(handler) => handler.get_summary(&child),
+                    None => Some(format!("{}: {}", SUBCOMMANDS, nested.join(", "))),
+                };
+                match aliases.get(&child) {
+                    Some(aliases) => (format!("{} ({})", child, aliases.join(", ")), summary),
+                    None => (child, summary),
+                }
+            })
+            .collect()
+    }
+
+    fn run_alias(&mut self, args: &[String], writer: &mut dyn Write) -> io::Result<Result<(), CliError>> {
+        let (definition, rest) = match args.split_first() {
+            Some(split) => split,
+            None => {
+                for (name, command) in self.aliases.entries() {
+                    writeln!(writer, "{} {}", ALIAS, alias_definition(name, command))?;
+                }
+                return Ok(Ok(()));
+            }
+        };
+        let (name, command) = match definition.split_once(ALIAS_SEPARATOR) {
+            Some(("", _)) => {
+                let message = format!("invalid value '{}' for {}", definition, ALIAS_DEFINITION);
+                return Ok(Err(CliError::ArgumentParseFailure(message)));
+            }
+            Some(split) => split,
+            None if rest.is_empty() => {
+                return match self.aliases.get(definition) {
+                    Some(command) => {
+                        writeln!(writer, "{} {}", ALIAS, alias_definition(definition, command))?;
+                        Ok(Ok(()))
+                    }
+                    None => {
+                        let message = format!("{}: {}", definition, ALIAS_NOT_FOUND);
+                        Ok(Err(CliError::ExecutionError(message)))
+                    }
+                };
+            }
+            None => {
+                return Ok(Err(CliError::InvalidNumberOfArguments {
+                    min: 0,
+                    max: Some(1),
+                    given: args.len(),
+                }))
+            }
+        };
+        if is_reserved(name) {
+            let message = format!("{}: {}", name, ALIAS_RESERVED);
+            return Ok(Err(CliError::ExecutionError(message)));
         }
+        // the first word of the command line is kept as entered, so quotes within it are preserved
+        let words: Vec<String> = std::iter::once(command.to_owned())
+            .filter(|word| !word.is_empty())
+            .chain(rest.iter().map(|word| tokenizer::quote(word)))
+            .collect();
+        Ok(self.aliases.set(name, &words.join(" ")).map_err(|e| match e.kind() {
+            io::ErrorKind::InvalidInput => {
+                CliError::ArgumentParseFailure(format!("invalid value '{}' for {}", definition, ALIAS_DEFINITION))
+            }
+            _ => alias_save_error(e),
+        }))
+    }
+
+    fn run_unalias(&mut self, args: &[String]) -> Result<(), CliError> {
+        if args.is_empty() {
+            return Err(CliError::InvalidNumberOfArguments {
+                min: 1,
+                max: None,
+                given: 0,
+            });
+        }
+        for name in args {
+            if !self.aliases.remove(name).map_err(alias_save_error)? {
+                return Err(CliError::ExecutionError(format!("{}: {}", name, ALIAS_NOT_FOUND)));
+            }
+        }
+        Ok(())
+    }
+
+    fn run_set(&mut self, args: &[String]) -> Result<(), CliError> {
+        let (name, value) = match args.split_first() {
+            Some(split) => split,
+            None => {
+                return Err(CliError::InvalidNumberOfArguments {
+                    min: 1,
+                    max: None,
+                    given: 0,
+                })
+            }
+        };
+        if !variable::is_valid_name(name) {
+            let message = format!("invalid value '{}' for {}", name, VARIABLE_NAME);
+            return Err(CliError::ArgumentParseFailure(message));
+        }
+        self.variables.set(name, &value.join(" "));
+        Ok(())
+    }
+
+    fn run_unset(&mut self, args: &[String]) -> Result<(), CliError> {
+        if args.is_empty() {
+            return Err(CliError::InvalidNumberOfArguments {
+                min: 1,
+                max: None,
+                given: 0,
+            });
+        }
+        for name in args {
+            if !self.variables.remove(name) {
+                return Err(CliError::ExecutionError(format!("{}: {}", name, VARIABLE_NOT_FOUND)));
+            }
+        }
+        Ok(())
+    }
+
+    fn print_variables(&self, args: &[String], writer: &mut dyn Write) -> io::Result<Result<(), CliError>> {
+        if !args.is_empty() {
+            return Ok(Err(CliError::InvalidNumberOfArguments {
+                min: 0,
+                max: None,
+                given: args.len(),
+            }));
+        }
+        for (name, value) in self.variables.entries() {
+            writeln!(writer, "{}={}", name, tokenizer::quote(value))?;
+        }
+        Ok(Ok(()))
+    }
+
+    fn print_history(&self, args: &[String], writer: &mut dyn Write) -> io::Result<Result<(), CliError>> {
+        let entries = self.history.entries();
+        let count = match args {
+            [] => entries.len(),
+            [count] => match count.parse::<usize>() {
+                Ok(count) => count,
+                Err(e) => {
+                    let message = format!("invalid value '{}' for <count>: {}", count, e);
+                    return Ok(Err(CliError::ArgumentParseFailure(message)));
+                }
+            },
+            _ => {
+                return Ok(Err(CliError::InvalidNumberOfArguments {
+                    min: 0,
+                    max: Some(1),
+                    given: args.len(),
+                }))
+            }
+        };
+        let skip = entries.len().saturating_sub(count);
+        let width = entries.len().to_string().len();
+        for (index, entry) in entries.iter().enumerate().skip(skip) {
+            writeln!(writer, "{:>width$}  {}", index + 1, entry, width = width)?;
+        }
+        Ok(Ok(()))
+    }
+}
+
+/// Formats the given alias as it is defined with the alias command, e.g. `ls='list --all'`.
+fn alias_definition(name: &str, command: &str) -> String {
+    format!(
+        "{}{}{}",
+        tokenizer::quote(name),
+        ALIAS_SEPARATOR,
+        tokenizer::quote(command)
+    )
+}
+
+fn alias_save_error(e: io::Error) -> CliError {
+    CliError::ExecutionError(format!("failed to save aliases: {}", e))
+}
+
+/// Opens the file at the given path for writing the output or error of a command, creating it if it does not exist.
+///
+/// # Arguments
+/// `path` - The path of the file.
+/// `append` - Whether to append to the file rather than truncating it.
+fn open_redirection(path: &str, append: bool) -> io::Result<File> {
+    OpenOptions::new()
+        .write(true)
+        .create(true)
+        .append(append)
+        .truncate(!append)
+        .open(path)
+}
+
+fn redirection_error(path: &str, error: io::Error) -> CliError {
+    CliError::RedirectionError {
+        path: path.to_owned(),
+        error,
+    }
+}
+
+/// Gets the number of insertions, deletions, substitutions and transpositions of adjacent characters needed to
+/// change one string into the other.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    // distances[i][j] is the distance between the first i characters of a and the first j characters of b
+    let mut distances = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in distances.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, distance) in distances[0].iter_mut().enumerate() {
+        *distance = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            let mut distance = cmp::min(
+                cmp::min(distances[i - 1][j] + 1, distances[i][j - 1] + 1),
+                distances[i - 1][j - 1] + cost,
+            );
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                distance = cmp::min(distance, distances[i - 2][j - 2] + 1);
+            }
+            distances[i][j] = distance;
+        }
+    }
+    distances[a.len()][b.len()]
+}
+
+fn dispatch(
+    handler: &dyn CliHandler,
+    command: &str,
+    args: Vec<String>,
+    writer: &mut dyn Write,
+) -> Result<(), CliError> {
+    match handler.get_spec(command) {
+        Some(spec) => handler.handle_arguments(command, spec.parse(args)?, writer),
+        None => handler.handle_command(command, args, writer),
     }
-    (command, vars)
 }
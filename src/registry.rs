@@ -0,0 +1,325 @@
+use std::collections::HashMap;
+use std::sync::{Arc, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::vec::Vec;
+
+use crate::manager::RESERVED_COMMANDS;
+use crate::{CliHandler, RegistrationError};
+
+/// The character separating the words of a subcommand, e.g. `db backup create`.
+pub const COMMAND_SEPARATOR: char = ' ';
+
+/// The identifier assigned to a handler when it is added to a [CliRegistry].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct HandlerId(u64);
+
+/// The handlers responsible for the commands of a [crate::manager::CliManager]. The registry is a shared handle:
+/// clones refer to the same handlers, so a clone obtained from [crate::manager::CliManager::registry()] can add and
+/// remove handlers from another thread while the manager is running, and the changes apply to the next command.
+///
+/// The commands of a handler are queried once when it is added, unless [CliHandler::has_dynamic_commands()] returns
+/// true, in which case they are queried each time a command is looked up. A handler whose commands change rarely can
+/// instead call [CliRegistry::refresh_handler()] after each change.
+#[derive(Clone, Default)]
+pub struct CliRegistry {
+    inner: Arc<RwLock<Registrations>>,
+}
+
+#[derive(Default)]
+struct Registrations {
+    commands: HashMap<String, (HandlerId, Arc<dyn CliHandler>)>,
+    // handlers without dynamic commands remain registered while they are responsible for no commands, so that they
+    // can still be refreshed and removed
+    handlers: HashMap<HandlerId, Arc<dyn CliHandler>>,
+    dynamic: Vec<(HandlerId, Arc<dyn CliHandler>)>,
+    next_id: u64,
+}
+
+impl CliRegistry {
+    /// Constructs a new, empty CliRegistry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the given CliHandler. All commands returned by [CliHandler::get_commands()] will now be forwarded to
+    /// this handler.
+    ///
+    /// The commands of a handler with dynamic commands are not checked; any command that is in [RESERVED_COMMANDS]
+    /// or is handled by a handler without dynamic commands or an earlier dynamic handler is ignored.
+    ///
+    /// # Arguments
+    /// `handler` - A reference to the handler to add. This reference is cloned for retention by the registry.
+    ///
+    /// # Returns
+    /// The identifier of the handler, which can be passed to [CliRegistry::remove_handler_id()].
+    ///
+    /// # Errors
+    /// A [RegistrationError] if any command of the handler is in [RESERVED_COMMANDS] or is already handled by another
+    /// handler, in which case none of the commands are added.
+    pub fn add_handler(&self, handler: Arc<dyn CliHandler>) -> Result<HandlerId, RegistrationError> {
+        self.register(handler, false)
+    }
+
+    /// Adds the given CliHandler, replacing any handler already responsible for one of its commands. All commands
+    /// returned by [CliHandler::get_commands()] will now be forwarded to this handler.
+    ///
+    /// # Arguments
+    /// `handler` - A reference to the handler to add. This reference is cloned for retention by the registry.
+    ///
+    /// # Returns
+    /// The identifier of the handler, which can be passed to [CliRegistry::remove_handler_id()].
+    ///
+    /// # Errors
+    /// A [RegistrationError] if any command of the handler is in [RESERVED_COMMANDS], in which case none of the
+    /// commands are added.
+    pub fn replace_handler(&self, handler: Arc<dyn CliHandler>) -> Result<HandlerId, RegistrationError> {
+        self.register(handler, true)
+    }
+
+    /// Removes the given handler and all commands forwarded to it.
+    ///
+    /// # Arguments
+    /// `handler` - A reference to the handler to remove, compared by identity.
+    ///
+    /// # Returns
+    /// True if the handler was removed, even if it was responsible for no commands.
+    pub fn remove_handler(&self, handler: &Arc<dyn CliHandler>) -> bool {
+        let target = Arc::as_ptr(handler) as *const ();
+        self.remove_where(|_, registered| Arc::as_ptr(registered) as *const () == target)
+    }
+
+    /// Removes the handler with the given identifier and all commands forwarded to it.
+    ///
+    /// # Arguments
+    /// `id` - The identifier returned when the handler was added.
+    ///
+    /// # Returns
+    /// True if the handler was removed, even if it was responsible for no commands.
+    pub fn remove_handler_id(&self, id: HandlerId) -> bool {
+        self.remove_where(|registered, _| *registered == id)
+    }
+
+    /// Queries the commands of the handler with the given identifier again, after they have changed. Commands that
+    /// the handler no longer returns are removed, and new commands are added. A handler that returns no commands
+    /// remains registered, so that it can be refreshed again later.
+    ///
+    /// # Arguments
+    /// `id` - The identifier returned when the handler was added.
+    ///
+    /// # Returns
+    /// True if the handler was found, or false if it has been removed or has dynamic commands.
+    ///
+    /// # Errors
+    /// A [RegistrationError] if any new command of the handler is in [RESERVED_COMMANDS] or is already handled by
+    /// another handler, in which case the commands of the handler are unchanged.
+    pub fn refresh_handler(&self, id: HandlerId) -> Result<bool, RegistrationError> {
+        let handler = self.read().handlers.get(&id).map(Arc::clone);
+        let handler = match handler {
+            Some(handler) => handler,
+            None => return Ok(false),
+        };
+        let commands = sorted_commands(handler.as_ref());
+        let mut registrations = self.write();
+        if !registrations.handlers.contains_key(&id) {
+            // the handler was removed while its commands were being queried
+            return Ok(false);
+        }
+        registrations.check(handler.as_ref(), &commands, Some(id))?;
+        registrations.commands.retain(|_, (registered, _)| *registered != id);
+        registrations.insert(id, &handler, commands);
+        Ok(true)
+    }
+
+    /// Removes the given command, leaving the other commands of its handler available. Commands of handlers with
+    /// dynamic commands cannot be removed individually.
+    ///
+    /// # Arguments
+    /// `command` - The command to remove.
+    ///
+    /// # Returns
+    /// True if the command was removed.
+    pub fn remove_command(&self, command: &str) -> bool {
+        self.write().commands.remove(command).is_some()
+    }
+
+    /// Gets the available commands, sorted by name.
+    pub fn commands(&self) -> Vec<String> {
+        self.handlers().into_iter().map(|(command, _)| command).collect()
+    }
+
+    /// Gets the handler responsible for the given command.
+    pub fn get(&self, command: &str) -> Option<Arc<dyn CliHandler>> {
+        let dynamic = {
+            let registrations = self.read();
+            if let Some((_, handler)) = registrations.commands.get(command) {
+                return Some(Arc::clone(handler));
+            }
+            registrations.dynamic.clone()
+        };
+        if is_reserved(command) {
+            return None;
+        }
+        // the lock is released before querying handlers, which may use the registry themselves
+        dynamic
+            .into_iter()
+            .map(|(_, handler)| handler)
+            .find(|handler| handler.get_commands().contains(command))
+    }
+
+    /// Finds the command named by the longest sequence of leading words, e.g. `db backup create` for the words
+    /// `db backup create daily`.
+    ///
+    /// # Arguments
+    /// `words` - The words of a command line.
+    ///
+    /// # Returns
+    /// The name of the command, the handler responsible for it and the number of words consumed by its name.
+    pub fn resolve<S: AsRef<str>>(&self, words: &[S]) -> Option<(String, Arc<dyn CliHandler>, usize)> {
+        (1..=words.len()).rev().find_map(|len| {
+            let name = join(&words[..len]);
+            self.get(&name).map(|handler| (name, handler, len))
+        })
+    }
+
+    /// Gets the commands in the namespace formed by the given words, e.g. `db backup create` and `db restore` for
+    /// the namespace `db`, sorted by name. The namespace formed by no words contains all commands.
+    ///
+    /// # Arguments
+    /// `namespace` - The leading words shared by the commands.
+    pub fn subcommands<S: AsRef<str>>(&self, namespace: &[S]) -> Vec<String> {
+        let prefix = join(namespace);
+        self.commands()
+            .into_iter()
+            .filter(|command| {
+                prefix.is_empty()
+                    || command
+                        .strip_prefix(&prefix)
+                        .is_some_and(|rest| rest.starts_with(COMMAND_SEPARATOR))
+            })
+            .collect()
+    }
+
+    /// Gets the available commands and the handlers responsible for them, sorted by command.
+    pub(crate) fn handlers(&self) -> Vec<(String, Arc<dyn CliHandler>)> {
+        let (mut handlers, dynamic): (HashMap<String, Arc<dyn CliHandler>>, _) = {
+            let registrations = self.read();
+            let handlers = registrations
+                .commands
+                .iter()
+                .map(|(command, (_, handler))| (command.clone(), Arc::clone(handler)))
+                .collect();
+            (handlers, registrations.dynamic.clone())
+        };
+        for (_, handler) in dynamic {
+            for command in handler.get_commands() {
+                if !is_reserved(command) && !handlers.contains_key(command) {
+                    handlers.insert(command.to_owned(), Arc::clone(&handler));
+                }
+            }
+        }
+        let mut handlers: Vec<(String, Arc<dyn CliHandler>)> = handlers.into_iter().collect();
+        handlers.sort_by(|(a, _), (b, _)| a.cmp(b));
+        handlers
+    }
+
+    fn register(&self, handler: Arc<dyn CliHandler>, replace: bool) -> Result<HandlerId, RegistrationError> {
+        let dynamic = handler.has_dynamic_commands();
+        let commands = if dynamic {
+            Vec::new()
+        } else {
+            sorted_commands(handler.as_ref())
+        };
+        let mut registrations = self.write();
+        if !replace {
+            registrations.check(handler.as_ref(), &commands, None)?;
+        } else if let Some(command) = commands.iter().find(|command| is_reserved(command)) {
+            return Err(RegistrationError::Reserved {
+                command: command.to_string(),
+                handler: handler.get_name().to_owned(),
+            });
+        }
+        let id = HandlerId(registrations.next_id);
+        registrations.next_id += 1;
+        if dynamic {
+            registrations.dynamic.push((id, handler));
+        } else {
+            registrations.insert(id, &handler, commands);
+            registrations.handlers.insert(id, handler);
+        }
+        Ok(id)
+    }
+
+    fn remove_where(&self, matches: impl Fn(&HandlerId, &Arc<dyn CliHandler>) -> bool) -> bool {
+        let mut registrations = self.write();
+        let count = registrations.handlers.len() + registrations.dynamic.len();
+        registrations.handlers.retain(|id, handler| !matches(id, handler));
+        registrations.dynamic.retain(|(id, handler)| !matches(id, handler));
+        let Registrations { commands, handlers, .. } = &mut *registrations;
+        commands.retain(|_, (id, _)| handlers.contains_key(id));
+        registrations.handlers.len() + registrations.dynamic.len() < count
+    }
+
+    fn read(&self) -> RwLockReadGuard<'_, Registrations> {
+        // the registrations remain consistent if a thread panics while holding the lock
+        self.inner.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, Registrations> {
+        self.inner.write().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Registrations {
+    /// Checks that the given commands are not reserved or handled by a handler other than the one with the given
+    /// identifier.
+    fn check(
+        &self,
+        handler: &dyn CliHandler,
+        commands: &[&'static str],
+        id: Option<HandlerId>,
+    ) -> Result<(), RegistrationError> {
+        for command in commands {
+            if is_reserved(command) {
+                return Err(RegistrationError::Reserved {
+                    command: command.to_string(),
+                    handler: handler.get_name().to_owned(),
+                });
+            }
+            match self.commands.get(*command) {
+                Some((registered, existing)) if Some(*registered) != id => {
+                    return Err(RegistrationError::Conflict {
+                        command: command.to_string(),
+                        existing: existing.get_name().to_owned(),
+                        handler: handler.get_name().to_owned(),
+                    })
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn insert(&mut self, id: HandlerId, handler: &Arc<dyn CliHandler>, commands: Vec<&'static str>) {
+        for command in commands {
+            self.commands.insert(command.to_owned(), (id, Arc::clone(handler)));
+        }
+    }
+}
+
+fn sorted_commands(handler: &dyn CliHandler) -> Vec<&'static str> {
+    let mut commands: Vec<&'static str> = handler.get_commands().into_iter().collect();
+    commands.sort_unstable();
+    commands
+}
+
+pub(crate) fn is_reserved(command: &str) -> bool {
+    // built-in commands are matched before subcommands, so they reserve every command in their namespace
+    let first = command.split(COMMAND_SEPARATOR).next().unwrap_or_default();
+    RESERVED_COMMANDS
+        .iter()
+        .any(|reserved| reserved.eq_ignore_ascii_case(first))
+}
+
+fn join<S: AsRef<str>>(words: &[S]) -> String {
+    let words: Vec<&str> = words.iter().map(AsRef::as_ref).collect();
+    words.join(&COMMAND_SEPARATOR.to_string())
+}
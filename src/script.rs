@@ -0,0 +1,98 @@
+use std::error::Error;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::vec::Vec;
+
+use crate::CliError;
+
+/// The character that starts a comment line in a script.
+pub const COMMENT: char = '#';
+
+/// The options controlling how a script is executed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ScriptOptions {
+    /// Whether the prompt is written before each command. Defaults to `false`.
+    pub show_prompt: bool,
+    /// Whether each command is written before it is executed. Defaults to `false`.
+    pub echo: bool,
+    /// Whether execution stops at the first command that fails. Defaults to `false`, in which case the remaining
+    /// commands are executed.
+    pub stop_on_error: bool,
+}
+
+/// An error that occurred while executing a line of a script.
+#[derive(Debug)]
+pub struct ScriptError {
+    line: usize,
+    command: String,
+    error: CliError,
+}
+
+impl ScriptError {
+    /// Constructs a new ScriptError.
+    ///
+    /// # Arguments
+    /// `line` - The 1-based number of the line on which the command appears.
+    /// `command` - The command as it appears in the script.
+    /// `error` - The error that occurred while executing the command.
+    pub fn new(line: usize, command: String, error: CliError) -> Self {
+        ScriptError { line, command, error }
+    }
+
+    /// Gets the 1-based number of the line on which the command appears.
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    /// Gets the command as it appears in the script.
+    pub fn command(&self) -> &str {
+        &self.command
+    }
+
+    /// Gets the error that occurred while executing the command.
+    pub fn error(&self) -> &CliError {
+        &self.error
+    }
+}
+
+impl Display for ScriptError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "Line {}: {}", self.line, self.error)
+    }
+}
+
+impl Error for ScriptError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+/// A summary of the execution of a script.
+#[derive(Debug, Default)]
+pub struct ScriptSummary {
+    pub(crate) executed: usize,
+    pub(crate) failures: Vec<ScriptError>,
+    pub(crate) exited: bool,
+}
+
+impl ScriptSummary {
+    /// Gets the number of commands that were executed, including those that failed.
+    pub fn executed(&self) -> usize {
+        self.executed
+    }
+
+    /// Gets the errors of the commands that failed, in the order they were executed.
+    pub fn failures(&self) -> &[ScriptError] {
+        &self.failures
+    }
+
+    /// Gets whether the script executed the exit command.
+    pub fn exited(&self) -> bool {
+        self.exited
+    }
+
+    /// Gets whether every command that was executed succeeded.
+    pub fn is_success(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
@@ -0,0 +1,548 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+use std::vec::Vec;
+
+use crate::CliError;
+
+/// An enumeration of the types to which an argument may be converted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ArgType {
+    /// A signed integer, converted to [ArgValue::Int].
+    Int,
+    /// A floating point number, converted to [ArgValue::Float].
+    Float,
+    /// A boolean, converted to [ArgValue::Bool]. Accepts `true`/`false`, `yes`/`no`, `on`/`off` and `1`/`0`,
+    /// ignoring case.
+    Bool,
+    /// Any text, converted to [ArgValue::String].
+    String,
+    /// One of the given values, converted to [ArgValue::String].
+    Choice(Vec<&'static str>),
+    /// A file system path, converted to [ArgValue::Path].
+    Path,
+}
+
+impl Display for ArgType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match &self {
+            ArgType::Int => write!(f, "integer"),
+            ArgType::Float => write!(f, "number"),
+            ArgType::Bool => write!(f, "boolean"),
+            ArgType::String => write!(f, "string"),
+            ArgType::Choice(choices) => write!(f, "one of {}", choices.join(", ")),
+            ArgType::Path => write!(f, "path"),
+        }
+    }
+}
+
+/// An enumeration of the number of values an argument accepts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Arity {
+    /// Exactly one value must be given.
+    Required,
+    /// Zero or one value may be given.
+    Optional,
+    /// Any number of values may be given, consuming all remaining arguments.
+    Variadic,
+}
+
+/// An enumeration of the values an argument may be converted to.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ArgValue {
+    /// The value of an [ArgType::Int] argument.
+    Int(i64),
+    /// The value of an [ArgType::Float] argument.
+    Float(f64),
+    /// The value of an [ArgType::Bool] argument.
+    Bool(bool),
+    /// The value of an [ArgType::String] or [ArgType::Choice] argument.
+    String(String),
+    /// The value of an [ArgType::Path] argument.
+    Path(PathBuf),
+}
+
+/// The specification of a single positional argument.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArgSpec {
+    name: &'static str,
+    arg_type: ArgType,
+    arity: Arity,
+    default: Option<&'static str>,
+}
+
+impl ArgSpec {
+    /// Constructs a new, required ArgSpec.
+    ///
+    /// # Arguments
+    /// `name` - The name of the argument, used in usage lines and error messages.
+    /// `arg_type` - The type to which the argument is converted.
+    pub fn new(name: &'static str, arg_type: ArgType) -> Self {
+        ArgSpec {
+            name,
+            arg_type,
+            arity: Arity::Required,
+            default: None,
+        }
+    }
+
+    /// Marks the argument as optional.
+    pub fn optional(mut self) -> Self {
+        self.arity = Arity::Optional;
+        self
+    }
+
+    /// Marks the argument as variadic, consuming all remaining arguments.
+    pub fn variadic(mut self) -> Self {
+        self.arity = Arity::Variadic;
+        self
+    }
+
+    /// Marks the argument as optional, using the given value when the argument is not given.
+    ///
+    /// # Arguments
+    /// `default` - The value to use, which is converted as if it had been given.
+    pub fn default_value(mut self, default: &'static str) -> Self {
+        self.arity = Arity::Optional;
+        self.default = Some(default);
+        self
+    }
+
+    /// Gets the name of the argument.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Gets the type to which the argument is converted.
+    pub fn arg_type(&self) -> &ArgType {
+        &self.arg_type
+    }
+
+    /// Gets the number of values the argument accepts.
+    pub fn arity(&self) -> Arity {
+        self.arity
+    }
+
+    /// Gets the value used when the argument is not given.
+    pub fn default(&self) -> Option<&'static str> {
+        self.default
+    }
+
+    fn convert(&self, value: &str) -> Result<ArgValue, CliError> {
+        convert(&self.arg_type, value, &format!("<{}>", self.name))
+    }
+}
+
+impl Display for ArgSpec {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.arity {
+            Arity::Required => write!(f, "<{}>", self.name),
+            Arity::Optional => write!(f, "[{}]", self.name),
+            Arity::Variadic => write!(f, "[{}...]", self.name),
+        }
+    }
+}
+
+/// The prefix of an option given by its long name, e.g. `--verbose`. When given alone, ends option parsing so that
+/// all remaining arguments are positional.
+pub const LONG_OPTION_PREFIX: &str = "--";
+/// The prefix of one or more options given by their short names, e.g. `-v` or `-abc`.
+pub const SHORT_OPTION_PREFIX: char = '-';
+
+/// The specification of a single option, given by its long name (`--name`) or short name (`-n`) anywhere in the
+/// arguments of a command.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OptionSpec {
+    name: &'static str,
+    short: Option<char>,
+    value_type: Option<ArgType>,
+}
+
+impl OptionSpec {
+    /// Constructs a new OptionSpec for a flag, which does not take a value. A flag may be repeated, e.g. `-vvv`.
+    ///
+    /// # Arguments
+    /// `name` - The long name of the option, without the [LONG_OPTION_PREFIX].
+    pub fn flag(name: &'static str) -> Self {
+        OptionSpec {
+            name,
+            short: None,
+            value_type: None,
+        }
+    }
+
+    /// Constructs a new OptionSpec for an option that takes a value, given as `--name value`, `--name=value`,
+    /// `-n value` or `-nvalue`. The option may be repeated to give multiple values. A value starting with
+    /// [LONG_OPTION_PREFIX] must be given as `--name=value` or `-nvalue`.
+    ///
+    /// # Arguments
+    /// `name` - The long name of the option, without the [LONG_OPTION_PREFIX].
+    /// `value_type` - The type to which the values of the option are converted.
+    pub fn value(name: &'static str, value_type: ArgType) -> Self {
+        OptionSpec {
+            name,
+            short: None,
+            value_type: Some(value_type),
+        }
+    }
+
+    /// Sets the short name of the option.
+    pub fn short(mut self, short: char) -> Self {
+        self.short = Some(short);
+        self
+    }
+
+    /// Gets the long name of the option.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Gets the short name of the option.
+    pub fn short_name(&self) -> Option<char> {
+        self.short
+    }
+
+    /// Gets the type to which the values of the option are converted, or `None` if the option is a flag.
+    pub fn value_type(&self) -> Option<&ArgType> {
+        self.value_type.as_ref()
+    }
+
+    fn convert(&self, value: &str) -> Result<ArgValue, CliError> {
+        match &self.value_type {
+            Some(value_type) => convert(value_type, value, &format!("{}{}", LONG_OPTION_PREFIX, self.name)),
+            None => Ok(ArgValue::Bool(true)),
+        }
+    }
+}
+
+impl Display for OptionSpec {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "[")?;
+        if let Some(short) = self.short {
+            write!(f, "{}{}|", SHORT_OPTION_PREFIX, short)?;
+        }
+        write!(f, "{}{}", LONG_OPTION_PREFIX, self.name)?;
+        if self.value_type.is_some() {
+            write!(f, " <{}>", self.name)?;
+        }
+        write!(f, "]")
+    }
+}
+
+/// The specification of the positional arguments and options accepted by a command. Arguments are matched in the
+/// order they are declared, so optional arguments must follow required arguments, and a variadic argument must be last.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CommandSpec {
+    args: Vec<ArgSpec>,
+    options: Vec<OptionSpec>,
+}
+
+impl CommandSpec {
+    /// Constructs a new CommandSpec that accepts no arguments.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends the given argument to the specification.
+    ///
+    /// # Panics
+    /// If the argument follows a variadic argument, or is required and follows an optional argument, as it could never
+    /// be matched.
+    pub fn arg(mut self, arg: ArgSpec) -> Self {
+        match self.args.last().map(ArgSpec::arity) {
+            Some(Arity::Variadic) => panic!("argument <{}> follows a variadic argument", arg.name),
+            Some(Arity::Optional) if arg.arity == Arity::Required => {
+                panic!("required argument <{}> follows an optional argument", arg.name)
+            }
+            _ => {}
+        }
+        self.args.push(arg);
+        self
+    }
+
+    /// Appends the given option to the specification.
+    pub fn option(mut self, option: OptionSpec) -> Self {
+        self.options.push(option);
+        self
+    }
+
+    /// Gets the arguments of the specification, in the order they are matched.
+    pub fn args(&self) -> &[ArgSpec] {
+        &self.args
+    }
+
+    /// Gets the options of the specification.
+    pub fn options(&self) -> &[OptionSpec] {
+        &self.options
+    }
+
+    /// Gets the usage line of the given command according to this specification,
+    /// e.g. `calculate [-v|--verbose] <name> <num1> <operator> <num2>`.
+    pub fn usage(&self, command: &str) -> String {
+        let mut usage = command.to_owned();
+        for option in &self.options {
+            usage.push(' ');
+            usage.push_str(&option.to_string());
+        }
+        for arg in &self.args {
+            usage.push(' ');
+            usage.push_str(&arg.to_string());
+        }
+        usage
+    }
+
+    /// Separates the options from the positional arguments, then validates both against this specification and
+    /// converts them to their declared types. An argument starting with [SHORT_OPTION_PREFIX] followed by a digit or
+    /// `.` is treated as a positional argument, so that negative numbers need not follow [LONG_OPTION_PREFIX].
+    ///
+    /// # Errors
+    /// [CliError::InvalidNumberOfArguments] if too few or too many positional arguments are given, or
+    /// [CliError::ArgumentParseFailure] naming the argument or option if a value cannot be converted, an option is
+    /// unknown, or an option is missing its value.
+    pub fn parse(&self, args: Vec<String>) -> Result<ParsedArgs, CliError> {
+        let (positionals, options) = self.parse_options(&args)?;
+
+        let min = self.args.iter().filter(|arg| arg.arity == Arity::Required).count();
+        let max = if self.args.iter().any(|arg| arg.arity == Arity::Variadic) {
+            None
+        } else {
+            Some(self.args.len())
+        };
+        if positionals.len() < min || max.is_some_and(|max| positionals.len() > max) {
+            return Err(CliError::InvalidNumberOfArguments {
+                min,
+                max: max.filter(|max| *max != min),
+                given: positionals.len(),
+            });
+        }
+
+        let mut values = HashMap::new();
+        let mut remaining = positionals.iter();
+        for spec in &self.args {
+            let converted = match spec.arity {
+                Arity::Variadic => remaining
+                    .by_ref()
+                    .map(|arg| spec.convert(arg))
+                    .collect::<Result<_, _>>()?,
+                _ => match remaining.next().map(String::as_str).or(spec.default) {
+                    Some(arg) => vec![spec.convert(arg)?],
+                    None => continue,
+                },
+            };
+            values.insert(spec.name, converted);
+        }
+        Ok(ParsedArgs {
+            values,
+            options,
+            positionals,
+            raw: args,
+        })
+    }
+
+    fn parse_options(&self, args: &[String]) -> Result<(Vec<String>, Options), CliError> {
+        let mut positionals = Vec::new();
+        let mut options: Options = HashMap::new();
+        let mut remaining = args.iter();
+        while let Some(arg) = remaining.next() {
+            if arg == LONG_OPTION_PREFIX {
+                positionals.extend(remaining.by_ref().cloned());
+            } else if let Some(long) = arg.strip_prefix(LONG_OPTION_PREFIX) {
+                let (name, inline_value) = match long.split_once('=') {
+                    Some((name, value)) => (name, Some(value)),
+                    None => (long, None),
+                };
+                let option = self
+                    .options
+                    .iter()
+                    .find(|option| option.name == name)
+                    .ok_or_else(|| unknown_option(&format!("{}{}", LONG_OPTION_PREFIX, name)))?;
+                let value = match (&option.value_type, inline_value) {
+                    (Some(_), Some(value)) => value,
+                    (Some(_), None) => next_value(&mut remaining, option)?,
+                    (None, Some(_)) => {
+                        return Err(CliError::ArgumentParseFailure(format!(
+                            "option '{}{}' does not take a value",
+                            LONG_OPTION_PREFIX, option.name
+                        )))
+                    }
+                    (None, None) => "",
+                };
+                options.entry(option.name).or_default().push(option.convert(value)?);
+            } else if let Some(shorts) = arg
+                .strip_prefix(SHORT_OPTION_PREFIX)
+                .filter(|shorts| is_short_options(shorts))
+            {
+                for (index, short) in shorts.char_indices() {
+                    let option = self
+                        .options
+                        .iter()
+                        .find(|option| option.short == Some(short))
+                        .ok_or_else(|| unknown_option(&format!("{}{}", SHORT_OPTION_PREFIX, short)))?;
+                    if option.value_type.is_none() {
+                        options.entry(option.name).or_default().push(option.convert("")?);
+                        continue;
+                    }
+                    let attached = &shorts[index + short.len_utf8()..];
+                    let value = if attached.is_empty() {
+                        next_value(&mut remaining, option)?
+                    } else {
+                        attached
+                    };
+                    options.entry(option.name).or_default().push(option.convert(value)?);
+                    break;
+                }
+            } else {
+                positionals.push(arg.clone());
+            }
+        }
+        Ok((positionals, options))
+    }
+}
+
+type Options = HashMap<&'static str, Vec<ArgValue>>;
+
+fn is_short_options(shorts: &str) -> bool {
+    match shorts.chars().next() {
+        Some(first) => !first.is_ascii_digit() && first != '.',
+        None => false,
+    }
+}
+
+fn unknown_option(option: &str) -> CliError {
+    CliError::ArgumentParseFailure(format!("unknown option '{}'", option))
+}
+
+/// Takes the value of the given option from the following argument, unless it is another long option or the end of the
+/// options, which is more likely a forgotten value than a value starting with [LONG_OPTION_PREFIX].
+fn next_value<'a>(remaining: &mut std::slice::Iter<'a, String>, option: &OptionSpec) -> Result<&'a str, CliError> {
+    match remaining.next() {
+        Some(value) if !value.starts_with(LONG_OPTION_PREFIX) => Ok(value),
+        _ => Err(missing_value(option)),
+    }
+}
+
+fn missing_value(option: &OptionSpec) -> CliError {
+    CliError::ArgumentParseFailure(format!(
+        "missing value for option '{}{}'",
+        LONG_OPTION_PREFIX, option.name
+    ))
+}
+
+fn convert(arg_type: &ArgType, value: &str, label: &str) -> Result<ArgValue, CliError> {
+    let converted = match arg_type {
+        ArgType::Int => value.parse().map(ArgValue::Int).map_err(|e| e.to_string()),
+        ArgType::Float => value.parse().map(ArgValue::Float).map_err(|e| e.to_string()),
+        ArgType::Bool => match value.to_ascii_lowercase().as_str() {
+            "true" | "yes" | "on" | "1" => Ok(ArgValue::Bool(true)),
+            "false" | "no" | "off" | "0" => Ok(ArgValue::Bool(false)),
+            _ => Err(format!("expected {}", arg_type)),
+        },
+        ArgType::String => Ok(ArgValue::String(value.to_owned())),
+        ArgType::Choice(choices) => {
+            if choices.contains(&value) {
+                Ok(ArgValue::String(value.to_owned()))
+            } else {
+                Err(format!("expected {}", arg_type))
+            }
+        }
+        ArgType::Path => Ok(ArgValue::Path(PathBuf::from(value))),
+    };
+    converted.map_err(|e| CliError::ArgumentParseFailure(format!("invalid value '{}' for {}: {}", value, label, e)))
+}
+
+/// The arguments of a command, separated into positional arguments and options, then validated and converted
+/// according to a [CommandSpec]. Values are retrieved by the name of the argument or the long name of the option; a
+/// flag has the value `true` for each time it was given.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ParsedArgs {
+    values: HashMap<&'static str, Vec<ArgValue>>,
+    options: HashMap<&'static str, Vec<ArgValue>>,
+    positionals: Vec<String>,
+    raw: Vec<String>,
+}
+
+impl ParsedArgs {
+    /// Gets the value of the given argument or option. Returns `None` if it was not given and has no default, the
+    /// first value if the argument is variadic, or the last value if the option was repeated.
+    pub fn get(&self, name: &str) -> Option<&ArgValue> {
+        match self.values.get(name) {
+            Some(values) => values.first(),
+            None => self.options.get(name).and_then(|values| values.last()),
+        }
+    }
+
+    /// Gets all values of the given argument or option. Returns an empty slice if it was not given.
+    pub fn get_all(&self, name: &str) -> &[ArgValue] {
+        self.values
+            .get(name)
+            .or_else(|| self.options.get(name))
+            .map_or(&[], Vec::as_slice)
+    }
+
+    /// Gets whether the given option was given at least once.
+    pub fn has_option(&self, name: &str) -> bool {
+        self.options.contains_key(name)
+    }
+
+    /// Gets the number of times the given option was given.
+    pub fn option_count(&self, name: &str) -> usize {
+        self.options.get(name).map_or(0, Vec::len)
+    }
+
+    /// Gets the value of the given [ArgType::Int] argument.
+    pub fn get_int(&self, name: &str) -> Option<i64> {
+        match self.get(name) {
+            Some(ArgValue::Int(value)) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// Gets the value of the given [ArgType::Float] argument.
+    pub fn get_float(&self, name: &str) -> Option<f64> {
+        match self.get(name) {
+            Some(ArgValue::Float(value)) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// Gets the value of the given [ArgType::Bool] argument.
+    pub fn get_bool(&self, name: &str) -> Option<bool> {
+        match self.get(name) {
+            Some(ArgValue::Bool(value)) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// Gets the value of the given [ArgType::String] or [ArgType::Choice] argument.
+    pub fn get_str(&self, name: &str) -> Option<&str> {
+        match self.get(name) {
+            Some(ArgValue::String(value)) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Gets the value of the given [ArgType::Path] argument.
+    pub fn get_path(&self, name: &str) -> Option<&Path> {
+        match self.get(name) {
+            Some(ArgValue::Path(value)) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Gets the positional arguments as they were given, before conversion.
+    pub fn positionals(&self) -> &[String] {
+        &self.positionals
+    }
+
+    /// Gets the arguments as they were given, before options were separated and values were converted.
+    pub fn raw(&self) -> &[String] {
+        &self.raw
+    }
+
+    /// Consumes the parsed arguments, returning the arguments as they were given, before options were separated and
+    /// values were converted.
+    pub fn into_raw(self) -> Vec<String> {
+        self.raw
+    }
+}
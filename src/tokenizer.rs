@@ -0,0 +1,577 @@
+use std::error::Error;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::iter::{Enumerate, Peekable};
+use std::mem;
+use std::str::Chars;
+use std::vec::Vec;
+
+use crate::variable;
+
+/// The character used to escape the character that follows it.
+pub const ESCAPE: char = '\\';
+/// The character used to quote text literally.
+pub const SINGLE_QUOTE: char = '\'';
+/// The character used to quote text while still allowing escapes.
+pub const DOUBLE_QUOTE: char = '"';
+/// The character that introduces a reference to a variable or the output of a command, e.g. `$NAME`, `${NAME}` or
+/// `$(command)`.
+pub const VARIABLE: char = '$';
+/// The characters that form the operators separating commands, `;`, `&&`, `||` and `|`, and the redirections `<`, `>`
+/// and `>>`.
+const OPERATOR_CHARS: [char; 5] = [';', '&', '|', '<', '>'];
+/// The file descriptor that precedes `>` or `>>` to redirect the error of a command, as in `2> path`.
+const ERROR_DESCRIPTOR: char = '2';
+
+/// An enumeration of the operators that separate the commands of a command line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operator {
+    /// `;`, which runs the following command regardless of the outcome of the preceding command.
+    Sequence,
+    /// `&&`, which runs the following command only if the preceding command succeeded.
+    And,
+    /// `||`, which runs the following command only if the preceding command failed.
+    Or,
+}
+
+impl Display for Operator {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match &self {
+            Operator::Sequence => write!(f, ";"),
+            Operator::And => write!(f, "&&"),
+            Operator::Or => write!(f, "||"),
+        }
+    }
+}
+
+/// An enumeration of the kinds of errors that can occur while tokenizing input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseErrorKind {
+    /// Indicates a single quote was opened but never closed.
+    UnterminatedSingleQuote,
+    /// Indicates a double quote was opened but never closed.
+    UnterminatedDoubleQuote,
+    /// Indicates the input ended with an escape character that has nothing to escape.
+    TrailingEscape,
+    /// Indicates an operator was not preceded by a command, or `&&` or `||` was not followed by one.
+    MissingCommand,
+    /// Indicates a redirection was not followed by the path of a file.
+    MissingRedirectionTarget,
+    /// Indicates a `${` was not closed or did not enclose a valid variable name.
+    BadSubstitution,
+    /// Indicates a `$(` was never closed.
+    UnterminatedSubstitution,
+}
+
+impl Display for ParseErrorKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match &self {
+            ParseErrorKind::UnterminatedSingleQuote => write!(f, "unterminated single quote"),
+            ParseErrorKind::UnterminatedDoubleQuote => write!(f, "unterminated double quote"),
+            ParseErrorKind::TrailingEscape => write!(f, "trailing escape character"),
+            ParseErrorKind::MissingCommand => write!(f, "missing command"),
+            ParseErrorKind::MissingRedirectionTarget => write!(f, "missing redirection target"),
+            ParseErrorKind::BadSubstitution => write!(f, "bad substitution"),
+            ParseErrorKind::UnterminatedSubstitution => write!(f, "unterminated command substitution"),
+        }
+    }
+}
+
+/// An error that occurred while tokenizing input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    kind: ParseErrorKind,
+    column: usize,
+}
+
+impl ParseError {
+    /// Constructs a new ParseError.
+    ///
+    /// # Arguments
+    /// `kind` - The kind of error that occurred.
+    /// `column` - The 1-based character position in the input at which the offending construct starts.
+    pub fn new(kind: ParseErrorKind, column: usize) -> Self {
+        ParseError { kind, column }
+    }
+
+    /// Gets the kind of error that occurred.
+    pub fn kind(&self) -> ParseErrorKind {
+        self.kind
+    }
+
+    /// Gets the 1-based character position in the input at which the offending construct starts.
+    pub fn column(&self) -> usize {
+        self.column
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at column {}", self.kind, self.column)
+    }
+}
+
+impl Error for ParseError {}
+
+type Input<'a> = Peekable<Enumerate<Chars<'a>>>;
+
+/// An enumeration of the parts of a word of a command, which are concatenated once any references are expanded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WordPart {
+    /// Literal text.
+    Text(String),
+    /// `$NAME` or `${NAME}`, which is replaced by the value of the variable with the given name.
+    Variable(String),
+    /// `$(command)`, which is replaced by the output of the given command line, without leading and trailing
+    /// whitespace.
+    Command(String),
+}
+
+/// A word of a command, given by its parts. A word without parts is empty, e.g. `''`.
+pub type Word = Vec<WordPart>;
+
+/// An enumeration of the redirections of the input, output or error of a command to or from a file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Redirection {
+    /// `< path`, which reads the input of the command from the file, or appends its lines to the arguments of a
+    /// command that does not read its input (see [crate::CliHandler::reads_input()]).
+    Input(Word),
+    /// `> path` or `>> path`, which writes the output of the command to the file, truncating it or appending to it.
+    Output {
+        /// The path of the file.
+        path: Word,
+        /// Whether the output is appended to the file rather than replacing its contents.
+        append: bool,
+    },
+    /// `2> path` or `2>> path`, which writes the error of the command to the file, truncating it or appending to it.
+    Error {
+        /// The path of the file.
+        path: Word,
+        /// Whether the error is appended to the file rather than replacing its contents.
+        append: bool,
+    },
+}
+
+impl Redirection {
+    /// Gets the path of the file to or from which the stream is redirected.
+    pub fn path(&self) -> &Word {
+        match self {
+            Redirection::Input(path) | Redirection::Output { path, .. } | Redirection::Error { path, .. } => path,
+        }
+    }
+}
+
+/// A command of a pipeline: its words, tokenized like [tokenize] but keeping any references to be expanded, and the
+/// redirections of its streams, in the order in which they appear.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Command {
+    words: Vec<Word>,
+    redirections: Vec<Redirection>,
+}
+
+impl Command {
+    /// Constructs a new Command.
+    ///
+    /// # Arguments
+    /// `words` - The words of the command, starting with its name.
+    /// `redirections` - The redirections of the streams of the command.
+    pub fn new(words: Vec<Word>, redirections: Vec<Redirection>) -> Self {
+        Command { words, redirections }
+    }
+
+    /// Gets the words of the command, starting with its name.
+    pub fn words(&self) -> &[Word] {
+        &self.words
+    }
+
+    /// Gets the redirections of the streams of the command, in the order in which they appear.
+    pub fn redirections(&self) -> &[Redirection] {
+        &self.redirections
+    }
+
+    /// Appends the words and redirections of the given command to those of this command.
+    pub(crate) fn append(&mut self, other: Command) {
+        self.words.extend(other.words);
+        self.redirections.extend(other.redirections);
+    }
+}
+
+impl From<Vec<String>> for Command {
+    /// Constructs a Command from the given literal words, without redirections.
+    fn from(words: Vec<String>) -> Self {
+        let words = words
+            .into_iter()
+            .map(|word| {
+                let mut parts = Word::new();
+                push_text(&mut parts, &word);
+                parts
+            })
+            .collect();
+        Command::new(words, Vec::new())
+    }
+}
+
+/// The commands of a pipeline, e.g. `list | grep name`. The output of each command is the input of the next, unless
+/// it is redirected.
+pub type Pipeline = Vec<Command>;
+
+enum Token {
+    Word(Word),
+    /// An operator and the 1-based character position at which it starts.
+    Operator(Operator, usize),
+    /// A pipe (`|`) and the 1-based character position at which it appears.
+    Pipe(usize),
+    /// A redirection, with an empty path that is given by the following word, and the 1-based character position at
+    /// which it starts.
+    Redirect(Redirection, usize),
+}
+
+/// Splits the given input into tokens, following the quoting rules of a POSIX shell:
+/// - unquoted whitespace (as defined by [char::is_whitespace]) separates tokens
+/// - [ESCAPE] outside of quotes preserves the literal value of the following character
+/// - text between [SINGLE_QUOTE]s is taken literally
+/// - text between [DOUBLE_QUOTE]s is taken literally, except that [ESCAPE] preserves the literal value of a
+///   following `"`, `\`, `$` or `` ` ``
+///
+/// Quoted and unquoted text that is not separated by whitespace is concatenated into a single token, so
+/// `a"b c"'d'` yields `ab cd`. Empty quotes yield an empty token. [VARIABLE] is taken literally.
+///
+/// # Errors
+/// A [ParseError] if a quote is not terminated or the input ends with an unescaped [ESCAPE].
+pub fn tokenize(input: &str) -> Result<Vec<String>, ParseError> {
+    let tokens = scan(input, false)?;
+    Ok(tokens
+        .into_iter()
+        .filter_map(|token| match token {
+            Token::Word(word) => Some(literal(word)),
+            Token::Operator(..) | Token::Pipe(_) | Token::Redirect(..) => None,
+        })
+        .collect())
+}
+
+/// Splits the given input into pipelines of commands separated by unquoted operators, tokenizing each command like
+/// [tokenize]:
+/// - `|` passes the output of the preceding command to the following command, forming a pipeline
+/// - `;` runs the following pipeline regardless of the outcome of the preceding pipeline
+/// - `&&` runs the following pipeline only if the preceding pipeline succeeded
+/// - `||` runs the following pipeline only if the preceding pipeline failed
+///
+/// Each command may also redirect its streams to or from files, the path of which is the following word:
+/// - `< path` reads the input of the command from the file
+/// - `> path` and `>> path` write the output of the command to the file, truncating it or appending to it
+/// - `2> path` and `2>> path` write the error of the command to the file, truncating it or appending to it
+///
+/// Operators need not be separated from words by whitespace, so `stop;start` yields two pipelines and `list>out`
+/// redirects the output of `list`. A trailing `;` is ignored.
+///
+/// Outside of [SINGLE_QUOTE]s, [VARIABLE] followed by a name (see [variable::is_valid_name]), or by a name enclosed in
+/// braces, is a reference to a variable, e.g. `$host` or `${host}`, which forms a [WordPart::Variable] of its word.
+/// [VARIABLE] followed by a command line enclosed in parentheses, e.g. `$(create session)`, forms a
+/// [WordPart::Command] of its word. Otherwise [VARIABLE] is taken literally, as it is when escaped.
+///
+/// # Returns
+/// Each pipeline along with the operator that precedes it. The first pipeline is preceded by [Operator::Sequence].
+///
+/// # Errors
+/// A [ParseError] if a quote is not terminated, the input ends with an unescaped [ESCAPE], an operator is not
+/// preceded by a command, `|`, `&&` or `||` is not followed by a command, a redirection is not followed by a path,
+/// `${` is not closed or does not enclose a valid name, or `$(` is not closed or does not enclose a valid command
+/// line.
+pub fn split_commands(input: &str) -> Result<Vec<(Operator, Pipeline)>, ParseError> {
+    let mut pipelines = Vec::new();
+    let mut pipeline = Vec::new();
+    let mut command = Command::default();
+    // the position of the first redirection of a command that has no words yet
+    let mut redirect_column = None;
+    let (mut operator, mut column) = (Operator::Sequence, 0);
+    let mut tokens = scan(input, true)?.into_iter();
+    while let Some(token) = tokens.next() {
+        match token {
+            Token::Word(word) => command.words.push(word),
+            Token::Redirect(redirection, redirection_column) => match tokens.next() {
+                Some(Token::Word(path)) => {
+                    command.redirections.push(with_path(redirection, path));
+                    redirect_column = redirect_column.or(Some(redirection_column));
+                }
+                _ => {
+                    return Err(ParseError::new(
+                        ParseErrorKind::MissingRedirectionTarget,
+                        redirection_column,
+                    ))
+                }
+            },
+            Token::Operator(_, next_column) | Token::Pipe(next_column) if command.words.is_empty() => {
+                return Err(ParseError::new(ParseErrorKind::MissingCommand, next_column))
+            }
+            Token::Pipe(next_column) => {
+                pipeline.push(mem::take(&mut command));
+                redirect_column = None;
+                column = next_column;
+            }
+            Token::Operator(next, next_column) => {
+                pipeline.push(mem::take(&mut command));
+                pipelines.push((operator, mem::take(&mut pipeline)));
+                redirect_column = None;
+                operator = next;
+                column = next_column;
+            }
+        }
+    }
+    if !command.words.is_empty() {
+        pipeline.push(command);
+        pipelines.push((operator, pipeline));
+    } else if let Some(redirect_column) = redirect_column {
+        return Err(ParseError::new(ParseErrorKind::MissingCommand, redirect_column));
+    } else if !pipeline.is_empty() || operator != Operator::Sequence {
+        return Err(ParseError::new(ParseErrorKind::MissingCommand, column));
+    }
+    Ok(pipelines)
+}
+
+/// Gets the words of the last command of the given input, i.e. those following the last unquoted operator or pipe,
+/// omitting redirections and their paths. Returns an empty Vec if the input could not be tokenized.
+pub(crate) fn last_command(input: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut redirected = false;
+    for token in scan(input, true).unwrap_or_default() {
+        match token {
+            Token::Word(_) if redirected => redirected = false,
+            Token::Word(word) => words.push(literal(word)),
+            Token::Redirect(..) => redirected = true,
+            Token::Operator(..) | Token::Pipe(_) => words.clear(),
+        }
+    }
+    words
+}
+
+/// Gets whether the given input ends with an unquoted redirection that is not yet followed by a path.
+pub(crate) fn ends_with_redirection(input: &str) -> bool {
+    matches!(scan(input, true).unwrap_or_default().last(), Some(Token::Redirect(..)))
+}
+
+/// Gets whether the given character forms an operator when it is not quoted.
+pub(crate) fn is_operator_char(c: char) -> bool {
+    OPERATOR_CHARS.contains(&c)
+}
+
+/// Splits the given input into tokens. Operators, redirections and references are only recognised in a command line,
+/// and are otherwise taken literally.
+fn scan(input: &str, command_line: bool) -> Result<Vec<Token>, ParseError> {
+    let mut tokens = Vec::new();
+    let mut current: Option<Word> = None;
+    let mut chars = input.chars().enumerate().peekable();
+    while let Some((index, c)) = chars.next() {
+        let error_redirect = c == ERROR_DESCRIPTOR && current.is_none() && matches!(chars.peek(), Some((_, '>')));
+        if command_line && error_redirect {
+            chars.next();
+            let append = chars.next_if(|(_, next)| *next == '>').is_some();
+            tokens.push(Token::Redirect(
+                Redirection::Error {
+                    path: Word::new(),
+                    append,
+                },
+                index + 1,
+            ));
+            continue;
+        }
+        if let Some(operator) = Some(c)
+            .filter(|_| command_line)
+            .and_then(|c| read_operator(c, index, &mut chars))
+        {
+            tokens.extend(current.take().map(Token::Word));
+            tokens.push(operator);
+            continue;
+        }
+        match c {
+            ESCAPE => match chars.next() {
+                Some((_, escaped)) => push_char(current.get_or_insert_with(Word::new), escaped),
+                None => return Err(ParseError::new(ParseErrorKind::TrailingEscape, index + 1)),
+            },
+            SINGLE_QUOTE => read_single_quoted(&mut chars, index, current.get_or_insert_with(Word::new))?,
+            DOUBLE_QUOTE => {
+                let word = current.get_or_insert_with(Word::new);
+                read_double_quoted(&mut chars, index, command_line, word)?
+            }
+            VARIABLE if command_line => read_reference(&mut chars, index, current.get_or_insert_with(Word::new))?,
+            c if c.is_whitespace() => tokens.extend(current.take().map(Token::Word)),
+            c => push_char(current.get_or_insert_with(Word::new), c),
+        }
+    }
+    tokens.extend(current.map(Token::Word));
+    Ok(tokens)
+}
+
+fn read_operator(c: char, index: usize, chars: &mut Input) -> Option<Token> {
+    let mut repeated = |c: char| chars.next_if(|(_, next)| *next == c).is_some();
+    let operator = match c {
+        ';' => Operator::Sequence,
+        '&' if repeated('&') => Operator::And,
+        '|' if repeated('|') => Operator::Or,
+        '|' => return Some(Token::Pipe(index + 1)),
+        '<' => return Some(Token::Redirect(Redirection::Input(Word::new()), index + 1)),
+        '>' => {
+            let append = repeated('>');
+            let redirection = Redirection::Output {
+                path: Word::new(),
+                append,
+            };
+            return Some(Token::Redirect(redirection, index + 1));
+        }
+        _ => return None,
+    };
+    Some(Token::Operator(operator, index + 1))
+}
+
+fn with_path(redirection: Redirection, path: Word) -> Redirection {
+    match redirection {
+        Redirection::Input(_) => Redirection::Input(path),
+        Redirection::Output { append, .. } => Redirection::Output { path, append },
+        Redirection::Error { append, .. } => Redirection::Error { path, append },
+    }
+}
+
+fn read_single_quoted(chars: &mut Input, start: usize, word: &mut Word) -> Result<(), ParseError> {
+    loop {
+        match chars.next() {
+            Some((_, SINGLE_QUOTE)) => return Ok(()),
+            Some((_, c)) => push_char(word, c),
+            None => return Err(ParseError::new(ParseErrorKind::UnterminatedSingleQuote, start + 1)),
+        }
+    }
+}
+
+fn read_double_quoted(chars: &mut Input, start: usize, references: bool, word: &mut Word) -> Result<(), ParseError> {
+    loop {
+        match chars.next() {
+            Some((_, DOUBLE_QUOTE)) => return Ok(()),
+            Some((_, ESCAPE)) => match chars.next() {
+                Some((_, c)) if matches!(c, DOUBLE_QUOTE | ESCAPE | VARIABLE | '`') => push_char(word, c),
+                Some((_, c)) => {
+                    push_char(word, ESCAPE);
+                    push_char(word, c);
+                }
+                None => return Err(ParseError::new(ParseErrorKind::UnterminatedDoubleQuote, start + 1)),
+            },
+            Some((index, VARIABLE)) if references => read_reference(chars, index, word)?,
+            Some((_, c)) => push_char(word, c),
+            None => return Err(ParseError::new(ParseErrorKind::UnterminatedDoubleQuote, start + 1)),
+        }
+    }
+}
+
+/// Reads the name of the variable referenced by the [VARIABLE] at the given position, either enclosed in braces or up
+/// to the first character that cannot be part of a name, or the command line enclosed in parentheses. A [VARIABLE]
+/// that is not followed by a name, a brace or a parenthesis is taken literally.
+fn read_reference(chars: &mut Input, start: usize, word: &mut Word) -> Result<(), ParseError> {
+    let mut name = String::new();
+    if chars.next_if(|(_, c)| *c == '(').is_some() {
+        let command = read_substitution(chars, start)?;
+        word.push(WordPart::Command(command));
+        return Ok(());
+    }
+    if chars.next_if(|(_, c)| *c == '{').is_some() {
+        loop {
+            match chars.next() {
+                Some((_, '}')) if variable::is_valid_name(&name) => break,
+                Some((_, c)) if c != '}' => name.push(c),
+                _ => return Err(ParseError::new(ParseErrorKind::BadSubstitution, start + 1)),
+            }
+        }
+    } else {
+        while let Some((_, c)) = chars.next_if(|(_, c)| c.is_ascii_alphanumeric() || *c == '_') {
+            name.push(c);
+        }
+        if !variable::is_valid_name(&name) {
+            // e.g. `$1`, which is taken literally
+            push_char(word, VARIABLE);
+            push_text(word, &name);
+            return Ok(());
+        }
+    }
+    word.push(WordPart::Variable(name));
+    Ok(())
+}
+
+/// Reads the command line of the substitution starting with the [VARIABLE] at the given position, up to the closing
+/// parenthesis that is neither quoted nor escaped nor matched by an earlier opening parenthesis.
+fn read_substitution(chars: &mut Input, start: usize) -> Result<String, ParseError> {
+    let mut command = String::new();
+    let mut quote = None;
+    let mut depth = 0;
+    loop {
+        let c = match chars.next() {
+            Some((_, c)) => c,
+            None => return Err(ParseError::new(ParseErrorKind::UnterminatedSubstitution, start + 1)),
+        };
+        match quote {
+            Some(open) if c == open => quote = None,
+            Some(DOUBLE_QUOTE) | None if c == ESCAPE => {
+                command.push(c);
+                if let Some((_, escaped)) = chars.next() {
+                    command.push(escaped);
+                }
+                continue;
+            }
+            Some(_) => {}
+            None => match c {
+                SINGLE_QUOTE | DOUBLE_QUOTE => quote = Some(c),
+                '(' => depth += 1,
+                ')' if depth == 0 => break,
+                ')' => depth -= 1,
+                _ => {}
+            },
+        }
+        command.push(c);
+    }
+    // the command line starts two characters after the `$`
+    split_commands(&command).map_err(|e| ParseError::new(e.kind(), e.column() + start + 2))?;
+    Ok(command)
+}
+
+fn push_char(word: &mut Word, c: char) {
+    match word.last_mut() {
+        Some(WordPart::Text(text)) => text.push(c),
+        _ => word.push(WordPart::Text(c.to_string())),
+    }
+}
+
+fn push_text(word: &mut Word, text: &str) {
+    text.chars().for_each(|c| push_char(word, c));
+}
+
+/// Gets the text of the given word, writing each reference as it would be entered, e.g. `${host}`.
+fn literal(word: Word) -> String {
+    word.into_iter()
+        .map(|part| match part {
+            WordPart::Text(text) => text,
+            WordPart::Variable(name) => format!("{}{{{}}}", VARIABLE, name),
+            WordPart::Command(command) => format!("{}({})", VARIABLE, command),
+        })
+        .collect()
+}
+
+/// Quotes the given text, if necessary, so that [tokenize] and [split_commands] yield it as a single, unchanged token.
+/// Text that contains no whitespace, quotes, escapes, operator characters or [VARIABLE]s is returned unchanged;
+/// otherwise it is wrapped in [SINGLE_QUOTE]s.
+pub fn quote(text: &str) -> String {
+    let needs_quotes = text.is_empty()
+        || text.chars().any(|c| {
+            c.is_whitespace() || is_operator_char(c) || matches!(c, ESCAPE | SINGLE_QUOTE | DOUBLE_QUOTE | VARIABLE)
+        });
+    if !needs_quotes {
+        return text.to_owned();
+    }
+    let mut quoted = String::with_capacity(text.len() + 2);
+    quoted.push(SINGLE_QUOTE);
+    for c in text.chars() {
+        if c == SINGLE_QUOTE {
+            // close the quotes, escape the quote, then reopen the quotes
+            quoted.push(SINGLE_QUOTE);
+            quoted.push(ESCAPE);
+            quoted.push(SINGLE_QUOTE);
+        }
+        quoted.push(c);
+    }
+    quoted.push(SINGLE_QUOTE);
+    quoted
+}
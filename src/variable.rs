@@ -0,0 +1,75 @@
+use std::collections::BTreeMap;
+use std::env;
+
+/// The variables of a session, the values of which are expanded by the manager wherever `$NAME` or `${NAME}` appears
+/// outside of single quotes in a command line, e.g. `connect $host` for `connect example.com`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Variables {
+    entries: BTreeMap<String, String>,
+}
+
+impl Variables {
+    /// Constructs a new, empty Variables.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Constructs a new Variables seeded from the environment variables of the process. Environment variables whose
+    /// names are not valid (see [is_valid_name]) or whose names or values are not valid Unicode are ignored.
+    pub fn from_env() -> Self {
+        let mut variables = Variables::new();
+        for (name, value) in env::vars_os() {
+            if let (Ok(name), Ok(value)) = (name.into_string(), value.into_string()) {
+                if is_valid_name(&name) {
+                    variables.entries.insert(name, value);
+                }
+            }
+        }
+        variables
+    }
+
+    /// Gets the names of the variables and their values, sorted by name.
+    pub fn entries(&self) -> &BTreeMap<String, String> {
+        &self.entries
+    }
+
+    /// Gets the value of the given variable.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.entries.get(name).map(String::as_str)
+    }
+
+    /// Gets the number of variables.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Gets whether no variables have been set.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Sets the given variable, replacing any previous value.
+    ///
+    /// # Arguments
+    /// `name` - The name of the variable, which can only be referenced if it is valid (see [is_valid_name]).
+    /// `value` - The value of the variable.
+    pub fn set(&mut self, name: &str, value: &str) {
+        self.entries.insert(name.to_owned(), value.to_owned());
+    }
+
+    /// Removes the given variable.
+    ///
+    /// # Returns
+    /// True if the variable was set.
+    pub fn remove(&mut self, name: &str) -> bool {
+        self.entries.remove(name).is_some()
+    }
+}
+
+/// Gets whether the given text is a valid variable name: an ASCII letter or `_`, followed by any number of ASCII
+/// letters, digits and `_`.
+pub fn is_valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
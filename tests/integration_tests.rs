@@ -1,13 +1,28 @@
+// the original tests are kept as they were written, before these lints
+#![allow(clippy::from_str_radix_10, clippy::get_first, clippy::unused_io_amount)]
+
 use std::collections::HashSet;
-use std::io::{stdout, BufReader, Read, Write};
+use std::io::{stdout, BufRead, BufReader, Error, ErrorKind, Read, Write};
 use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time::Duration;
 
-use jdn_cli::manager::{CliManager, INVALID_COMMAND, PROMPT};
-use jdn_cli::CliError;
+use jdn_cli::alias::Aliases;
+use jdn_cli::completion::{bash_completion_script, Completions, COMPLETE_COMMAND};
+use jdn_cli::editor::LineEditor;
+use jdn_cli::filter::Filters;
+use jdn_cli::history::History;
+use jdn_cli::manager::{CliManager, DispatchPolicy, EofPolicy, ExitReason, EXIT_SUCCESS, INVALID_COMMAND, PROMPT};
+use jdn_cli::script::ScriptOptions;
+use jdn_cli::spec::{ArgSpec, ArgType, ArgValue, CommandSpec, OptionSpec, ParsedArgs};
+use jdn_cli::tokenizer::{
+    quote, split_commands, tokenize, Command, Operator, ParseError, ParseErrorKind, Pipeline, Redirection, WordPart,
+};
+use jdn_cli::variable::Variables;
 use jdn_cli::CliHandler;
+use jdn_cli::{CliCommands, CliError, CliExecute, CliIoError, RegistrationError};
 
 #[test]
 fn test_manager_empty() -> std::io::Result<()> {
@@ -23,7 +38,7 @@ fn test_manager_empty() -> std::io::Result<()> {
         .name(String::from("JdnCli-Manager"))
         .spawn(move || {
             let mut manager = CliManager::with_reader_writer(BufReader::new(reader), writer);
-            manager.start();
+            manager.start()
         })
         .expect("failed to spawn thread");
     thread::sleep(Duration::from_millis(250));
@@ -50,8 +65,8 @@ fn test_manager_empty() -> std::io::Result<()> {
     print(invalid_command.to_vec(), None)?;
 
     // expect invalid command response
-    let invalid_response = INVALID_COMMAND.to_owned() + "\n";
-    let mut prompt_buf = [0; INVALID_COMMAND.len() + 1];
+    let invalid_response = format!("{} 'invalid'\n", INVALID_COMMAND);
+    let mut prompt_buf = vec![0; invalid_response.len()];
     out_buf.read(&mut prompt_buf)?;
     print(prompt_buf.to_vec(), Some(invalid_response))?;
 
@@ -66,7 +81,8 @@ fn test_manager_empty() -> std::io::Result<()> {
     print(exit_command.to_vec(), None)?;
 
     // expect manager to stop
-    manager_handle.join().unwrap();
+    let exit_reason = manager_handle.join().unwrap().expect("manager failed");
+    assert_eq!(exit_reason, ExitReason::Exit);
 
     Ok(())
 }
@@ -86,8 +102,8 @@ fn test_one_handler() -> std::io::Result<()> {
         .spawn(move || {
             let mut manager = CliManager::with_reader_writer(BufReader::new(reader), writer);
             let handler = TestHandler::new();
-            manager.add_handler(Arc::new(handler));
-            manager.start();
+            manager.add_handler(Arc::new(handler)).expect("failed to add handler");
+            manager.start()
         })
         .expect("failed to spawn thread");
     thread::sleep(Duration::from_millis(250));
@@ -147,7 +163,8 @@ fn test_one_handler() -> std::io::Result<()> {
     print(exit_command.to_vec(), None)?;
 
     // expect manager to stop
-    manager_handle.join().unwrap();
+    let exit_reason = manager_handle.join().unwrap().expect("manager failed");
+    assert_eq!(exit_reason, ExitReason::Exit);
 
     Ok(())
 }
@@ -166,9 +183,13 @@ fn test_two_handlers() -> std::io::Result<()> {
         .name(String::from("JdnCli-Manager"))
         .spawn(move || {
             let mut manager = CliManager::with_reader_writer(BufReader::new(reader), writer);
-            manager.add_handler(Arc::new(TestHandler::new()));
-            manager.add_handler(Arc::new(TestHandler2::new()));
-            manager.start();
+            manager
+                .add_handler(Arc::new(TestHandler::new()))
+                .expect("failed to add handler");
+            manager
+                .replace_handler(Arc::new(TestHandler2::new()))
+                .expect("failed to replace handler");
+            manager.start()
         })
         .expect("failed to spawn thread");
     thread::sleep(Duration::from_millis(250));
@@ -247,7 +268,8 @@ fn test_two_handlers() -> std::io::Result<()> {
     print(exit_command.to_vec(), None)?;
 
     // expect manager to stop
-    manager_handle.join().unwrap();
+    let exit_reason = manager_handle.join().unwrap().expect("manager failed");
+    assert_eq!(exit_reason, ExitReason::Exit);
 
     Ok(())
 }
@@ -267,8 +289,8 @@ fn test_complex_commands() -> std::io::Result<()> {
         .spawn(move || {
             let mut manager = CliManager::with_reader_writer(BufReader::new(reader), writer);
             let handler = TestHandler::new();
-            manager.add_handler(Arc::new(handler));
-            manager.start();
+            manager.add_handler(Arc::new(handler)).expect("failed to add handler");
+            manager.start()
         })
         .expect("failed to spawn thread");
     thread::sleep(Duration::from_millis(250));
@@ -397,11 +419,1787 @@ fn test_complex_commands() -> std::io::Result<()> {
     print(exit_command.to_vec(), None)?;
 
     // expect manager to stop
-    manager_handle.join().unwrap();
+    let exit_reason = manager_handle.join().unwrap().expect("manager failed");
+    assert_eq!(exit_reason, ExitReason::Exit);
+
+    Ok(())
+}
+
+#[test]
+fn test_end_of_input() -> std::io::Result<()> {
+    // represents stdin
+    let mut in_stream = TestBuffer::default();
+    // represents stdout
+    let out_stream = TestBuffer::default();
+
+    // start manager
+    let reader = in_stream.clone();
+    let writer = out_stream.clone();
+    let manager_handle = thread::Builder::new()
+        .name(String::from("JdnCli-Manager"))
+        .spawn(move || {
+            let mut manager = CliManager::with_reader_writer(BufReader::new(reader), writer);
+            manager
+                .add_handler(Arc::new(TestHandler::new()))
+                .expect("failed to add handler");
+            manager.start()
+        })
+        .expect("failed to spawn thread");
+    thread::sleep(Duration::from_millis(250));
+    let mut out_buf = BufReader::new(out_stream);
+
+    // expect prompt
+    let mut prompt_buf = [0; PROMPT.len()];
+    out_buf.read_exact(&mut prompt_buf)?;
+    print(prompt_buf.to_vec(), Some(PROMPT.to_owned()))?;
+
+    // send start command without a trailing newline, then end the input
+    let start_command = b"start";
+    in_stream.write_all(start_command)?;
+    in_stream.close();
+    print(start_command.to_vec(), None)?;
+
+    // expect start response
+    let started_response = String::from("started\n");
+    let mut prompt_buf = [0; 8];
+    out_buf.read_exact(&mut prompt_buf)?;
+    print(prompt_buf.to_vec(), Some(started_response))?;
+
+    // expect prompt
+    let mut prompt_buf = [0; PROMPT.len()];
+    out_buf.read_exact(&mut prompt_buf)?;
+    print(prompt_buf.to_vec(), Some(PROMPT.to_owned()))?;
+
+    // expect manager to stop
+    let exit_reason = manager_handle.join().unwrap().expect("manager failed");
+    assert_eq!(exit_reason, ExitReason::EndOfInput);
+
+    Ok(())
+}
+
+#[test]
+fn test_end_of_input_as_exit_command() {
+    let in_stream = TestBuffer::default();
+    in_stream.close();
+    let mut manager = CliManager::with_reader_writer(BufReader::new(in_stream), Vec::new());
+    manager.set_eof_policy(EofPolicy::ExitCommand);
+    assert_eq!(manager.start().expect("manager failed"), ExitReason::Exit);
+}
+
+#[test]
+fn test_end_of_input_callback() -> std::io::Result<()> {
+    // represents stdin
+    let in_stream = TestBuffer::default();
+    // represents stdout
+    let out_stream = TestBuffer::default();
+    in_stream.close();
+
+    // start manager, supplying more input at the first end of input only
+    let calls = Arc::new(AtomicUsize::new(0));
+    let callback_calls = Arc::clone(&calls);
+    let mut callback_stream = in_stream.clone();
+    let mut manager = CliManager::with_reader_writer(BufReader::new(in_stream), out_stream.clone());
+    manager
+        .add_handler(Arc::new(TestHandler::new()))
+        .expect("failed to add handler");
+    manager.set_eof_policy(EofPolicy::Callback(Box::new(move || {
+        if callback_calls.fetch_add(1, Ordering::SeqCst) == 0 {
+            callback_stream.write_all(b"start\n").expect("failed to supply input");
+        }
+        true
+    })));
+    // the manager, including its callback, can be moved to another thread
+    let exit_reason = thread::spawn(move || manager.start().expect("manager failed"))
+        .join()
+        .expect("manager thread failed");
+    assert_eq!(exit_reason, ExitReason::EndOfInput);
+    // the input remains at its end after the second call, so the manager stops rather than calling again
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+    // expect a prompt for each attempt to read
+    let mut out_buf = BufReader::new(out_stream);
+    let expected = format!("{0}{0}started\n{0}{0}", PROMPT);
+    let mut output_buf = vec![0; expected.len()];
+    out_buf.read_exact(&mut output_buf)?;
+    print(output_buf, Some(expected))?;
 
     Ok(())
 }
 
+#[test]
+fn test_tokenize() {
+    let cases: [(&str, &[&str]); 9] = [
+        ("", &[]),
+        ("cmd\targ1  arg2\u{3000}arg3", &["cmd", "arg1", "arg2", "arg3"]),
+        (
+            "cmd \"quoted arg\" 'single quoted'",
+            &["cmd", "quoted arg", "single quoted"],
+        ),
+        ("cmd a\"b c\"'d'", &["cmd", "ab cd"]),
+        ("cmd \"\" ''", &["cmd", "", ""]),
+        ("cmd escaped\\ space \\\"", &["cmd", "escaped space", "\""]),
+        ("cmd \"say \\\"hi\\\"\" \"C:\\dir\"", &["cmd", "say \"hi\"", "C:\\dir"]),
+        ("cmd 'no \\escape' '\"'", &["cmd", "no \\escape", "\""]),
+        ("cmd \"it's\"", &["cmd", "it's"]),
+    ];
+    for (input, expected) in cases.iter() {
+        assert_eq!(
+            tokenize(input).expect("failed to tokenize"),
+            *expected,
+            "input: {}",
+            input
+        );
+    }
+}
+
+#[test]
+fn test_tokenize_errors() {
+    let cases = [
+        ("cmd \"unterminated", ParseErrorKind::UnterminatedDoubleQuote, 5),
+        ("cmd ok 'unterminated", ParseErrorKind::UnterminatedSingleQuote, 8),
+        ("cmd \"ends with escape\\", ParseErrorKind::UnterminatedDoubleQuote, 5),
+        ("cmd trailing\\", ParseErrorKind::TrailingEscape, 13),
+    ];
+    for (input, kind, column) in cases.iter() {
+        assert_eq!(
+            tokenize(input),
+            Err(ParseError::new(*kind, *column)),
+            "input: {}",
+            input
+        );
+    }
+}
+
+#[test]
+fn test_split_commands() {
+    type Pipelines<'a> = &'a [(Operator, &'a [&'a [&'a str]])];
+    let cases: [(&str, Pipelines); 8] = [
+        ("", &[]),
+        ("stop", &[(Operator::Sequence, &[&["stop"]])]),
+        (
+            "stop;start x",
+            &[
+                (Operator::Sequence, &[&["stop"]]),
+                (Operator::Sequence, &[&["start", "x"]]),
+            ],
+        ),
+        (
+            "start && calculate x 1 + 2 || echo failed;",
+            &[
+                (Operator::Sequence, &[&["start"]]),
+                (Operator::And, &[&["calculate", "x", "1", "+", "2"]]),
+                (Operator::Or, &[&["echo", "failed"]]),
+            ],
+        ),
+        (
+            "echo 'a;b' \"&&\" c\\|\\| d&e '|f'",
+            &[(Operator::Sequence, &[&["echo", "a;b", "&&", "c||", "d&e", "|f"]])],
+        ),
+        ("echo ok ;", &[(Operator::Sequence, &[&["echo", "ok"]])]),
+        (
+            "history | grep -i start|tail -n 3",
+            &[(
+                Operator::Sequence,
+                &[&["history"], &["grep", "-i", "start"], &["tail", "-n", "3"]],
+            )],
+        ),
+        (
+            "list | sort && echo sorted",
+            &[
+                (Operator::Sequence, &[&["list"], &["sort"]]),
+                (Operator::And, &[&["echo", "sorted"]]),
+            ],
+        ),
+    ];
+    for (input, expected) in cases.iter() {
+        let expected: Vec<(Operator, Pipeline)> = expected
+            .iter()
+            .map(|(operator, pipeline)| {
+                let pipeline = pipeline
+                    .iter()
+                    .map(|words| Command::from(words.iter().map(|word| word.to_string()).collect::<Vec<_>>()))
+                    .collect();
+                (*operator, pipeline)
+            })
+            .collect();
+        assert_eq!(
+            split_commands(input).expect("failed to split"),
+            expected,
+            "input: {}",
+            input
+        );
+    }
+    assert_eq!(tokenize("stop;start|sort").unwrap(), ["stop;start|sort"]);
+    assert_eq!(Operator::And.to_string(), "&&");
+
+    let text = |text: &str| vec![WordPart::Text(text.to_owned())];
+    let redirected = Command::new(
+        vec![text("list"), text("2")],
+        vec![
+            Redirection::Output {
+                path: text("out"),
+                append: false,
+            },
+            Redirection::Input(text("in file")),
+            Redirection::Error {
+                path: text("err"),
+                append: true,
+            },
+        ],
+    );
+    assert_eq!(
+        split_commands("list 2 >out <'in file' 2>>err | sort").unwrap(),
+        [(
+            Operator::Sequence,
+            vec![redirected, Command::from(vec![String::from("sort")])]
+        )]
+    );
+    assert_eq!(tokenize("list>out").unwrap(), ["list>out"]);
+
+    let variable = |name: &str| WordPart::Variable(name.to_owned());
+    let referenced = Command::new(
+        vec![
+            text("connect"),
+            vec![variable("host")],
+            vec![
+                WordPart::Text(String::from("port=")),
+                variable("port"),
+                WordPart::Text(String::from("0")),
+            ],
+            vec![variable("user"), WordPart::Text(String::from(" at $host"))],
+            text("$host"),
+            text("$1 $ cost: 5$"),
+        ],
+        vec![Redirection::Output {
+            path: vec![variable("log")],
+            append: true,
+        }],
+    );
+    assert_eq!(
+        split_commands("connect $host port=${port}0 \"$user at \\$host\" '$host' '$1 $ cost: 5$' >>$log").unwrap(),
+        [(Operator::Sequence, vec![referenced])]
+    );
+    assert_eq!(tokenize("echo $host").unwrap(), ["echo", "$host"]);
+
+    let command = |line: &str| WordPart::Command(line.to_owned());
+    let substituted = Command::new(
+        vec![
+            text("attach"),
+            vec![command("create \"a b\" | grep ')'")],
+            vec![WordPart::Text(String::from("x")), command("echo $(echo y)")],
+        ],
+        Vec::new(),
+    );
+    assert_eq!(
+        split_commands("attach $(create \"a b\" | grep ')') x\"$(echo $(echo y))\"").unwrap(),
+        [(Operator::Sequence, vec![substituted])]
+    );
+
+    let errors = [
+        ("; stop", ParseErrorKind::MissingCommand, 1),
+        ("stop;;", ParseErrorKind::MissingCommand, 6),
+        ("stop &&", ParseErrorKind::MissingCommand, 6),
+        ("stop || ", ParseErrorKind::MissingCommand, 6),
+        ("stop && || start", ParseErrorKind::MissingCommand, 9),
+        ("| grep a", ParseErrorKind::MissingCommand, 1),
+        ("list |", ParseErrorKind::MissingCommand, 6),
+        ("list | | sort", ParseErrorKind::MissingCommand, 8),
+        ("list | && sort", ParseErrorKind::MissingCommand, 8),
+        ("list >", ParseErrorKind::MissingRedirectionTarget, 6),
+        ("list 2>> | sort", ParseErrorKind::MissingRedirectionTarget, 6),
+        ("list < > out", ParseErrorKind::MissingRedirectionTarget, 6),
+        ("> out", ParseErrorKind::MissingCommand, 1),
+        ("list | 2> err", ParseErrorKind::MissingCommand, 8),
+        ("echo ${host", ParseErrorKind::BadSubstitution, 6),
+        ("echo \"${}\"", ParseErrorKind::BadSubstitution, 7),
+        ("echo ${1x}", ParseErrorKind::BadSubstitution, 6),
+        ("echo $(echo", ParseErrorKind::UnterminatedSubstitution, 6),
+        ("echo $(echo 'a)", ParseErrorKind::UnterminatedSubstitution, 6),
+        ("echo $(echo &&)", ParseErrorKind::MissingCommand, 13),
+        ("stop 'unterminated; start", ParseErrorKind::UnterminatedSingleQuote, 6),
+    ];
+    for (input, kind, column) in errors.iter() {
+        assert_eq!(
+            split_commands(input),
+            Err(ParseError::new(*kind, *column)),
+            "input: {}",
+            input
+        );
+    }
+}
+
+#[test]
+fn test_chaining() {
+    let out_stream = TestBuffer::default();
+    let input = b"stop; start\ncalculate x 1 + 2 && echo done\nstop && calculate x 1 + 2 || echo failed\n\
+                  start a b || echo 'a;b' && exit\nstop\n";
+    let mut manager = CliManager::with_reader_writer(BufReader::new(&input[..]), out_stream.clone());
+    manager
+        .add_handler(Arc::new(TestHandler::new()))
+        .expect("failed to add handler");
+    assert_eq!(manager.start().expect("manager failed"), ExitReason::Exit);
+
+    let output = String::from_utf8(out_stream.buffer.lock().unwrap().clone()).unwrap();
+    let expected = [
+        "> stopped",
+        "started",
+        "> x is 3",
+        "done",
+        "> stopped",
+        "Execution error: TestHandler not started.",
+        "failed",
+        "> Invalid number of arguments: expected 0-1, received 2.",
+        "a;b",
+        "",
+    ];
+    assert_eq!(output, expected.join("\n"));
+
+    let outcome = manager.execute("start && stop && calculate x 1 + 2 && echo unreachable");
+    assert_eq!(outcome.output(), "started\nstopped\n");
+    assert!(matches!(outcome.result(), Err(CliError::ExecutionError(_))));
+    assert!(manager.execute("echo one; echo two").result().is_ok());
+    assert!(matches!(
+        manager.execute("echo one &&").result(),
+        Err(CliError::SyntaxError(e)) if e.kind() == ParseErrorKind::MissingCommand
+    ));
+    assert_eq!(manager.history().last(), Some("echo one &&"));
+    assert_eq!(manager.complete("stop; sta").candidates(), ["start"]);
+    assert_eq!(manager.complete("stop && echo 'a;b' || st").start(), 22);
+}
+
+#[test]
+fn test_pipelines() {
+    let mut manager = CliManager::with_reader_writer(BufReader::new(&b""[..]), Vec::new());
+    manager
+        .add_handler(Arc::new(TestHandler::new()))
+        .expect("failed to add handler");
+    manager.add_handler(Arc::new(Database)).expect("failed to add handler");
+    manager.add_handler(Arc::new(Filters)).expect("failed to add handler");
+
+    let cases = [
+        ("db backup list | grep week", "weekly\n"),
+        ("db backup list | grep -vi DAILY", "weekly\n"),
+        ("db backup list|grep -c l", "2\n"),
+        ("db backup list | sort -r", "weekly\ndaily\n"),
+        ("db backup list | head -n 1", "daily\n"),
+        ("db backup list | tail --lines=1", "weekly\n"),
+        ("db backup list | tail", "daily\nweekly\n"),
+        ("db backup list | wc", "2 2 13\n"),
+        ("db backup list | wc -lc", "2 13\n"),
+        ("db backup list | sort -r | head -n 1 | wc -w", "1\n"),
+        ("echo '10 b\n9 a\nx\n9 a' | sort -n -u", "x\n9 a\n10 b\n"),
+        ("echo 'b\na\nb' | sort -u", "a\nb\n"),
+        ("echo ignored | start", "started\n"),
+        ("grep anything", ""),
+        (
+            "stop | grep stopped && calculate x 1 + 2 | wc -l",
+            "stopped\nExecution error: TestHandler not started.\n0\n",
+        ),
+    ];
+    for (line, output) in cases.iter() {
+        let outcome = manager.execute(line);
+        assert!(outcome.result().is_ok(), "line: {}", line);
+        assert_eq!(outcome.output(), *output, "line: {}", line);
+    }
+    assert!(manager
+        .execute("history | tail -n 1")
+        .output()
+        .ends_with("  history | tail -n 1\n"));
+
+    assert!(matches!(
+        manager.execute("db backup list | grep").result(),
+        Err(CliError::InvalidNumberOfArguments { .. })
+    ));
+    assert_eq!(
+        manager
+            .execute("echo a | head -n -1")
+            .result()
+            .as_ref()
+            .unwrap_err()
+            .to_string(),
+        "Argument parse failure: invalid value '-1' for --lines: must not be negative"
+    );
+    assert!(matches!(
+        manager.execute("echo a |").result(),
+        Err(CliError::SyntaxError(e)) if e.kind() == ParseErrorKind::MissingCommand
+    ));
+    assert_eq!(manager.complete("db backup list | gr").candidates(), ["grep"]);
+    assert_eq!(
+        manager.complete("db backup list | sort --r").candidates(),
+        ["--reverse"]
+    );
+}
+
+#[test]
+fn test_redirection() {
+    let directory = std::env::temp_dir().join(format!("jdn-cli-redirection-{}", std::process::id()));
+    std::fs::create_dir_all(&directory).expect("failed to create directory");
+    let path = |name: &str| directory.join(name).to_string_lossy().into_owned();
+    let (output, error, missing) = (path("output.txt"), path("error.txt"), path("missing/output.txt"));
+    let read = |path: &str| std::fs::read_to_string(path).expect("failed to read file");
+
+    let out_stream = TestBuffer::default();
+    let mut manager = CliManager::with_reader_writer(BufReader::new(&b""[..]), out_stream.clone());
+    manager
+        .add_handler(Arc::new(TestHandler::new()))
+        .expect("failed to add handler");
+    manager.add_handler(Arc::new(Database)).expect("failed to add handler");
+    manager.add_handler(Arc::new(Filters)).expect("failed to add handler");
+
+    let cases = [
+        (format!("db backup list > {}", quote(&output)), "", "daily\nweekly\n"),
+        (format!("echo more>>{}", quote(&output)), "", "daily\nweekly\nmore\n"),
+        (
+            format!("db backup list | sort -r >{} | wc -l", quote(&output)),
+            "0\n",
+            "weekly\ndaily\n",
+        ),
+        (format!("grep -c ly < {}", quote(&output)), "2\n", "weekly\ndaily\n"),
+        (
+            format!("echo a | grep -c ly < {}", quote(&output)),
+            "2\n",
+            "weekly\ndaily\n",
+        ),
+        (format!("help > {} && echo saved", quote(&output)), "saved\n", ""),
+        (format!("start 2> {}", quote(&output)), "started\n", ""),
+    ];
+    for (line, expected_output, expected_file) in cases.iter() {
+        let outcome = manager.execute(line);
+        assert!(outcome.result().is_ok(), "line: {}", line);
+        assert_eq!(outcome.output(), *expected_output, "line: {}", line);
+        if !expected_file.is_empty() {
+            assert_eq!(read(&output), *expected_file, "line: {}", line);
+        }
+    }
+    assert!(read(&output).is_empty());
+
+    let outcome = manager.execute(&format!("stop; calculate x 1 + 2 2> {} || echo failed", quote(&error)));
+    assert_eq!(outcome.output(), "stopped\nfailed\n");
+    assert_eq!(read(&error), "Execution error: TestHandler not started.\n");
+    let outcome = manager.execute(&format!("calculate x 1 + 2 2>>{}", quote(&error)));
+    assert!(matches!(outcome.result(), Err(CliError::ExecutionError(_))));
+    assert!(outcome.output().is_empty());
+    assert_eq!(read(&error).lines().count(), 2);
+
+    let input = path("input.txt");
+    std::fs::write(&input, "1\n+\n2\n").expect("failed to write input");
+    let cases = [
+        (
+            format!("start; calculate total < {}; stop", quote(&input)),
+            "started\ntotal is 3\nstopped\n",
+        ),
+        (format!("echo < {} from file", quote(&input)), "from file 1 + 2\n"),
+        (format!("set sum < {}; echo $sum", quote(&input)), "1 + 2\n"),
+        (format!("wc -l < {}", quote(&input)), "3\n"),
+    ];
+    for (line, expected_output) in cases.iter() {
+        let outcome = manager.execute(line);
+        assert!(outcome.result().is_ok(), "line: {}", line);
+        assert_eq!(outcome.output(), *expected_output, "line: {}", line);
+    }
+
+    let summary = manager
+        .run_script(
+            format!("calculate x 1 + 2 2> {}\n", quote(&error)).as_bytes(),
+            ScriptOptions::default(),
+        )
+        .expect("script failed");
+    assert_eq!(summary.failures().len(), 1);
+    assert!(out_stream.buffer.lock().unwrap().is_empty());
+
+    for line in [
+        format!("echo a > {}", quote(&missing)),
+        format!("grep a < {}", quote(&missing)),
+    ]
+    .iter()
+    {
+        let outcome = manager.execute(line);
+        match outcome.result() {
+            Err(e @ CliError::RedirectionError { path, .. }) => {
+                assert_eq!(*path, missing);
+                assert_eq!(e.exit_code(), 5);
+            }
+            result => panic!("unexpected result for {}: {:?}", line, result),
+        }
+    }
+    assert!(matches!(
+        manager.execute("echo a >").result(),
+        Err(CliError::SyntaxError(e)) if e.kind() == ParseErrorKind::MissingRedirectionTarget
+    ));
+
+    let partial = path("out");
+    assert_eq!(
+        manager.complete(&format!("echo a > {}", partial)).candidates(),
+        [output.as_str()]
+    );
+    assert_eq!(
+        manager.complete(&format!("echo a 2>{}", partial)).candidates(),
+        [output]
+    );
+    assert_eq!(manager.complete("db backup list > out | gr").candidates(), ["grep"]);
+    std::fs::remove_dir_all(&directory).expect("failed to remove directory");
+}
+
+#[test]
+fn test_variables() {
+    let mut manager = CliManager::with_reader_writer(BufReader::new(&b""[..]), Vec::new());
+    manager
+        .add_handler(Arc::new(TestHandler::new()))
+        .expect("failed to add handler");
+
+    let cases = [
+        ("set host example.com", ""),
+        ("echo $host", "example.com\n"),
+        ("set greeting hello   world", ""),
+        (
+            "echo \"${greeting}!\" '$greeting' \\$host $ $1",
+            "hello world! $greeting $host $ $1\n",
+        ),
+        ("set n 1; start; calculate total $n + 2", "started\ntotal is 3\n"),
+        ("set n 2 && calculate total $n + 2", "total is 4\n"),
+        ("set cmd echo; $cmd via variable", "via variable\n"),
+        ("set empty; echo \"[$empty]\"", "[]\n"),
+        (
+            "vars",
+            "cmd=echo\nempty=''\ngreeting='hello world'\nhost=example.com\nn=2\n",
+        ),
+        ("unset cmd empty n", ""),
+        ("alias greet='echo \"$greeting\" from ${host}'", ""),
+        ("greet", "hello world from example.com\n"),
+        ("set greeting hi; greet", "hi from example.com\n"),
+        (
+            "help set",
+            "Usage: set <name> [value...]\nSets the given variable to the given value, which replaces $name or \
+                      ${name} in later commands.\n",
+        ),
+    ];
+    for (line, output) in cases.iter() {
+        let outcome = manager.execute(line);
+        assert!(outcome.result().is_ok(), "line: {}", line);
+        assert_eq!(outcome.output(), *output, "line: {}", line);
+    }
+    assert_eq!(manager.variables().len(), 2);
+    assert_eq!(manager.variables().get("host"), Some("example.com"));
+    assert_eq!(manager.history().last(), Some("help set"));
+
+    let errors = [
+        ("echo $missing", "Execution error: missing: undefined variable"),
+        ("echo a > ${missing}", "Execution error: missing: undefined variable"),
+        ("set 1x a", "Argument parse failure: invalid value '1x' for <name>"),
+        ("set", "Invalid number of arguments: expected 1, received 0."),
+        ("unset missing", "Execution error: missing: variable not found"),
+        ("vars all", "Invalid number of arguments: expected 0, received 1."),
+        ("echo ${host", "Syntax error: bad substitution at column 6"),
+    ];
+    for (line, error) in errors.iter() {
+        let outcome = manager.execute(line);
+        assert_eq!(
+            outcome.result().as_ref().unwrap_err().to_string(),
+            *error,
+            "line: {}",
+            line
+        );
+    }
+
+    assert_eq!(manager.complete("echo $h").candidates(), ["$host"]);
+    assert_eq!(manager.complete("echo \"$gr").candidates(), ["$greeting"]);
+    assert_eq!(manager.complete("unset g").candidates(), ["greeting"]);
+    assert_eq!(manager.complete("un").candidates(), ["unalias", "unset"]);
+
+    let variables = Variables::from_env();
+    assert_eq!(variables.get("PATH"), std::env::var("PATH").ok().as_deref());
+    assert!(variables
+        .entries()
+        .keys()
+        .all(|name| jdn_cli::variable::is_valid_name(name)));
+    let mut variables = Variables::new();
+    variables.set("id", "42");
+    manager.set_variables(variables);
+    assert_eq!(manager.execute("echo id=$id").output(), "id=42\n");
+    assert!(manager.execute("echo $host").result().is_err());
+}
+
+#[test]
+fn test_substitution() {
+    let mut manager = CliManager::with_reader_writer(BufReader::new(&b""[..]), Vec::new());
+    manager
+        .add_handler(Arc::new(TestHandler::new()))
+        .expect("failed to add handler");
+    manager.add_handler(Arc::new(Database)).expect("failed to add handler");
+    manager.add_handler(Arc::new(Filters)).expect("failed to add handler");
+
+    let cases = [
+        ("set id $(echo '  42  ')", ""),
+        ("echo id=$id", "id=42\n"),
+        (
+            "echo latest: $(db backup list | sort -r | head -n 1)",
+            "latest: weekly\n",
+        ),
+        ("echo \"[$(echo)]\" $(echo a; echo b)", "[] a\nb\n"),
+        ("echo $(echo $(echo nested))", "nested\n"),
+        ("echo $(echo ')') \\$(echo x) '$(echo y)'", ") $(echo x) $(echo y)\n"),
+        ("start; calculate total $(echo 1) + 2", "started\ntotal is 3\n"),
+        ("echo $(exit) done", " done\n"),
+    ];
+    for (line, output) in cases.iter() {
+        let outcome = manager.execute(line);
+        assert!(outcome.result().is_ok(), "line: {}", line);
+        assert!(!outcome.is_exit(), "line: {}", line);
+        assert_eq!(outcome.output(), *output, "line: {}", line);
+    }
+    assert_eq!(manager.history().last(), Some("echo $(exit) done"));
+
+    for line in ["echo [$(invalid; echo id-42)]", "echo [$(invalid | echo id-42)]"].iter() {
+        let outcome = manager.execute(line);
+        assert!(outcome.result().is_ok(), "line: {}", line);
+        assert_eq!(
+            outcome.output(),
+            "Invalid command 'invalid'\n[id-42]\n",
+            "line: {}",
+            line
+        );
+    }
+    let outcome = manager.execute("echo $(invalid; echo a b) | wc -w");
+    assert!(outcome.result().is_ok());
+    assert_eq!(outcome.output(), "Invalid command 'invalid'\n2\n");
+
+    let outcome = manager.execute("echo $(invalid) && echo unreachable");
+    assert!(matches!(
+        outcome.result(),
+        Err(CliError::InvalidCommand { command, .. }) if command == "invalid"
+    ));
+    assert!(outcome.output().is_empty());
+    assert!(matches!(
+        manager.execute("echo $(echo &&)").result(),
+        Err(CliError::SyntaxError(e)) if e.kind() == ParseErrorKind::MissingCommand && e.column() == 13
+    ));
+}
+
+#[test]
+fn test_syntax_error() {
+    let out_stream = TestBuffer::default();
+    let reader = BufReader::new(&b"start \"unterminated\n\t\nstart 'name'\n"[..]);
+    let mut manager = CliManager::with_reader_writer(reader, out_stream.clone());
+    manager
+        .add_handler(Arc::new(TestHandler::new()))
+        .expect("failed to add handler");
+    assert_eq!(manager.start().expect("manager failed"), ExitReason::EndOfInput);
+
+    let output = String::from_utf8(out_stream.buffer.lock().unwrap().clone()).unwrap();
+    assert_eq!(
+        output,
+        "> Syntax error: unterminated double quote at column 7\n> > started\n> "
+    );
+}
+
+#[test]
+fn test_help_text() {
+    let out_stream = TestBuffer::default();
+    let input = b"help\nhelp backup\nhelp restore\nhelp start\nhelp missing\nhelp exit\nhelp backup restore\n";
+    let mut manager = CliManager::with_reader_writer(BufReader::new(&input[..]), out_stream.clone());
+    manager
+        .add_handler(Arc::new(TestHandler::new()))
+        .expect("failed to add handler");
+    manager
+        .add_handler(Arc::new(DocumentedHandler))
+        .expect("failed to add handler");
+    assert_eq!(manager.start().expect("manager failed"), ExitReason::EndOfInput);
+
+    let output = String::from_utf8(out_stream.buffer.lock().unwrap().clone()).unwrap();
+    let expected = [
+        "> backup      Backs up the database",
+        "calculate",
+        "is-running",
+        "restore     Restores the database",
+        "start",
+        "stop",
+        "> Usage: backup <path> [--compress]",
+        "Writes a snapshot of the database to the given path, optionally compressing it.",
+        "> Usage: restore",
+        "Restores the database",
+        "> Usage: start",
+        &format!("> {} 'missing'", INVALID_COMMAND),
+        "> Usage: exit",
+        "Stops the command line interface.",
+        "> Invalid command 'backup restore'",
+        "> ",
+    ]
+    .join("\n");
+    assert_eq!(output, expected);
+}
+
+#[test]
+fn test_command_spec() {
+    let out_stream = TestBuffer::default();
+    let input = [
+        "scale 1.5 4",
+        "scale 2 3 cm front back",
+        "scale 2",
+        "scale two 3",
+        "scale 2 3.5",
+        "scale 2 3 km",
+        "toggle yes /tmp/file",
+        "toggle OFF",
+        "toggle maybe",
+        "toggle on a b",
+        "help scale",
+        "report title",
+        "report -vv --count 3 title -t a --tag=b rest",
+        "report -vn5 -- -t -10",
+        "report -ta title",
+        "report --count=-2 -1",
+        "report --verbose=yes title",
+        "report --count",
+        "report --count --verbose title",
+        "report -t -- title",
+        "report -x title",
+        "report --missing title",
+        "report --count three title",
+        "help report",
+    ]
+    .join("\n");
+    let mut manager = CliManager::with_reader_writer(BufReader::new(input.as_bytes()), out_stream.clone());
+    manager
+        .add_handler(Arc::new(SpecHandler))
+        .expect("failed to add handler");
+    assert_eq!(manager.start().expect("manager failed"), ExitReason::EndOfInput);
+
+    let output = String::from_utf8(out_stream.buffer.lock().unwrap().clone()).unwrap();
+    let expected = [
+        "> 6 m",
+        "> 6 cm front,back",
+        "> Invalid number of arguments: expected 2, received 1.",
+        "> Argument parse failure: invalid value 'two' for <value>: invalid float literal",
+        "> Argument parse failure: invalid value '3.5' for <factor>: invalid digit found in string",
+        "> Argument parse failure: invalid value 'km' for <unit>: expected one of mm, cm, m",
+        "> enabled at /tmp/file",
+        "> disabled",
+        "> Argument parse failure: invalid value 'maybe' for <enabled>: expected boolean",
+        "> Invalid number of arguments: expected 1-2, received 3.",
+        "> Usage: scale <value> <factor> [unit] [labels...]",
+        "> title verbosity=0 count=1 tags= rest=",
+        "> title verbosity=2 count=3 tags=a,b rest=rest",
+        "> -t verbosity=1 count=5 tags= rest=-10",
+        "> title verbosity=0 count=1 tags=a rest=",
+        "> -1 verbosity=0 count=-2 tags= rest=",
+        "> Argument parse failure: option '--verbose' does not take a value",
+        "> Argument parse failure: missing value for option '--count'",
+        "> Argument parse failure: missing value for option '--count'",
+        "> Argument parse failure: missing value for option '--tag'",
+        "> Argument parse failure: unknown option '-x'",
+        "> Argument parse failure: unknown option '--missing'",
+        "> Argument parse failure: invalid value 'three' for --count: invalid digit found in string",
+        "> Usage: report [-v|--verbose] [-n|--count <count>] [-t|--tag <tag>] <title> [rest...]",
+        "> ",
+    ]
+    .join("\n");
+    assert_eq!(output, expected);
+}
+
+#[test]
+#[should_panic(expected = "required argument <factor> follows an optional argument")]
+fn test_command_spec_required_after_optional() {
+    CommandSpec::new()
+        .arg(ArgSpec::new("value", ArgType::Float).optional())
+        .arg(ArgSpec::new("factor", ArgType::Int));
+}
+
+#[test]
+#[should_panic(expected = "argument <unit> follows a variadic argument")]
+fn test_command_spec_after_variadic() {
+    CommandSpec::new()
+        .arg(ArgSpec::new("labels", ArgType::String).variadic())
+        .arg(ArgSpec::new("unit", ArgType::String).optional());
+}
+
+#[test]
+fn test_derive_handler() {
+    let out_stream = TestBuffer::default();
+    let input = [
+        "add 5",
+        "add -2",
+        "show",
+        "add x",
+        "add",
+        "sum 1 2 3",
+        "show",
+        "reset",
+        "show",
+        "reset 7",
+        "show",
+        "reset 1 2",
+        "help",
+        "help add",
+        "help sum",
+    ]
+    .join("\n");
+    let mut manager = CliManager::with_reader_writer(BufReader::new(input.as_bytes()), out_stream.clone());
+    manager
+        .add_handler(Arc::new(Counter::default()))
+        .expect("failed to add handler");
+    assert_eq!(manager.start().expect("manager failed"), ExitReason::EndOfInput);
+
+    let output = String::from_utf8(out_stream.buffer.lock().unwrap().clone()).unwrap();
+    let expected = [
+        "> > > 3",
+        "> Argument parse failure: invalid value 'x' for <amount>: invalid digit found in string",
+        "> Invalid number of arguments: expected 1, received 0.",
+        "> > 9",
+        "> > 0",
+        "> > 7",
+        "> Invalid number of arguments: expected 0-1, received 2.",
+        "> add    Adds the given amount to the counter.",
+        "reset  Resets the counter, optionally to the given value.",
+        "show",
+        "sum    Adds all of the given amounts to the counter.",
+        "> Usage: add <amount>",
+        "Adds the given amount to the counter.",
+        "",
+        "Negative amounts are subtracted.",
+        "> Usage: sum [amounts...]",
+        "Adds all of the given amounts to the counter.",
+        "> ",
+    ]
+    .join("\n");
+    assert_eq!(output, expected);
+}
+
+#[test]
+fn test_derive_struct() {
+    assert_eq!(CopyFile::commands(), ["copy-file"].iter().cloned().collect());
+    let args = vec![String::from("a.txt"), String::from("b.txt")];
+    let parsed = CopyFile::parse("copy-file", args).expect("failed to parse");
+    assert_eq!(
+        parsed,
+        CopyFile {
+            source: String::from("a.txt"),
+            destination: Some(String::from("b.txt")),
+        }
+    );
+    let parsed = CopyFile::parse("copy-file", vec![String::from("a.txt")]).expect("failed to parse");
+    assert_eq!(parsed.destination, None);
+    assert_eq!(
+        CopyFile::usage("copy-file"),
+        Some(String::from("copy-file <source> [destination]"))
+    );
+    assert_eq!(CopyFile::summary("copy-file"), None);
+}
+
+#[test]
+fn test_script() {
+    let script = [
+        "# provision the calculator",
+        "start",
+        "",
+        "  calculate total 1 + 2",
+        "calculate total 1 plus 2",
+        "invalid",
+        "   # indented comment",
+        "stop",
+    ]
+    .join("\n");
+    let out_stream = TestBuffer::default();
+    let mut manager = CliManager::with_reader_writer(BufReader::new(&b""[..]), out_stream.clone());
+    manager
+        .add_handler(Arc::new(TestHandler::new()))
+        .expect("failed to add handler");
+    let summary = manager
+        .run_script(script.as_bytes(), ScriptOptions::default())
+        .expect("script failed");
+    assert_eq!(summary.executed(), 5);
+    assert!(!summary.exited());
+    assert!(!summary.is_success());
+    let failures: Vec<(usize, &str)> = summary
+        .failures()
+        .iter()
+        .map(|failure| (failure.line(), failure.command()))
+        .collect();
+    assert_eq!(failures, [(5, "calculate total 1 plus 2"), (6, "invalid")]);
+    assert!(matches!(
+        summary.failures()[0].error(),
+        CliError::ArgumentParseFailure(_)
+    ));
+    assert!(matches!(summary.failures()[1].error(), CliError::InvalidCommand { command, .. } if command == "invalid"));
+
+    let output = String::from_utf8(out_stream.buffer.lock().unwrap().clone()).unwrap();
+    let expected = [
+        "started",
+        "total is 3",
+        "Line 5: Argument parse failure: too many characters in string",
+        &format!("Line 6: {} 'invalid'", INVALID_COMMAND),
+        "stopped",
+        "",
+    ]
+    .join("\n");
+    assert_eq!(output, expected);
+}
+
+#[test]
+fn test_script_file() {
+    let path = std::env::temp_dir().join(format!("jdn-cli-script-{}.txt", std::process::id()));
+    std::fs::write(&path, "start\ncalculate total 4 x\nis-running\nexit\nstop\n").expect("failed to write script");
+    let out_stream = TestBuffer::default();
+    let mut manager = CliManager::with_reader_writer(BufReader::new(&b""[..]), out_stream.clone());
+    manager
+        .add_handler(Arc::new(TestHandler::new()))
+        .expect("failed to add handler");
+    let options = ScriptOptions {
+        show_prompt: true,
+        echo: true,
+        stop_on_error: true,
+    };
+    let summary = manager.run_script_file(&path, options).expect("script failed");
+    std::fs::remove_file(&path).expect("failed to remove script");
+    assert_eq!(summary.executed(), 2);
+    assert_eq!(summary.failures()[0].line(), 2);
+    assert!(!summary.exited());
+
+    let output = String::from_utf8(out_stream.buffer.lock().unwrap().clone()).unwrap();
+    let expected = [
+        "> start",
+        "started",
+        "> calculate total 4 x",
+        "Line 2: Invalid number of arguments: expected 4, received 3.",
+        "",
+    ]
+    .join("\n");
+    assert_eq!(output, expected);
+
+    let summary = manager
+        .run_script(&b"is-running\nexit\nstop\n"[..], ScriptOptions::default())
+        .expect("script failed");
+    assert_eq!(summary.executed(), 2);
+    assert!(summary.exited());
+    assert!(summary.is_success());
+
+    let missing = manager.run_script_file(path, ScriptOptions::default());
+    assert!(matches!(missing, Err(CliIoError::Read(_))));
+}
+
+#[test]
+fn test_run_args() {
+    let out_stream = TestBuffer::default();
+    let mut manager = CliManager::with_reader_writer(BufReader::new(&b""[..]), out_stream.clone());
+    manager
+        .add_handler(Arc::new(TestHandler::new()))
+        .expect("failed to add handler");
+    manager
+        .add_handler(Arc::new(SpecHandler))
+        .expect("failed to add handler");
+    let cases: [(&[&str], i32, &str); 9] = [
+        (&["tool", "scale", "1.5", "2", "cm"], EXIT_SUCCESS, "3 cm\n"),
+        (
+            &["tool", "calculate", "total", "1", "+", "2"],
+            1,
+            "Execution error: TestHandler not started.\n",
+        ),
+        (
+            &["tool", "scale", "1"],
+            2,
+            "Invalid number of arguments: expected 2, received 1.\n",
+        ),
+        (
+            &["tool", "scale", "x", "1"],
+            3,
+            "Argument parse failure: invalid value 'x' for <value>: invalid float literal\n",
+        ),
+        (&["tool", "missing"], 127, "Invalid command 'missing'\n"),
+        (&["tool", "exit"], EXIT_SUCCESS, ""),
+        (
+            &["tool", "help", "toggle"],
+            EXIT_SUCCESS,
+            "Usage: toggle <enabled> [path]\n",
+        ),
+        (
+            &["tool", "help", "scal"],
+            127,
+            "Invalid command 'scal'. Did you mean 'scale'?\n",
+        ),
+        (&["tool"], 2, ""),
+    ];
+    for (args, code, output) in cases.iter() {
+        out_stream.buffer.lock().unwrap().clear();
+        assert_eq!(manager.run_args(args.iter().cloned()).expect("failed to run"), *code);
+        let written = String::from_utf8(out_stream.buffer.lock().unwrap().clone()).unwrap();
+        if args.len() > 1 {
+            assert_eq!(written, *output, "args: {:?}", args);
+        } else {
+            assert!(written.starts_with("calculate\n"), "args: {:?}", args);
+        }
+    }
+}
+
+#[test]
+fn test_execute() {
+    let mut manager = CliManager::with_reader_writer(BufReader::new(&b""[..]), Vec::new());
+    manager
+        .add_handler(Arc::new(TestHandler::new()))
+        .expect("failed to add handler");
+
+    let outcome = manager.execute("calculate total 1 + 1");
+    assert_eq!(outcome.output(), "");
+    assert!(matches!(outcome.result(), Err(CliError::ExecutionError(_))));
+
+    let outcome = manager.execute("start");
+    assert_eq!(outcome.output(), "started\n");
+    assert!(outcome.result().is_ok());
+    assert!(!outcome.is_exit());
+
+    let outcome = manager.execute("calculate \"complex calculation\" 6 / 3");
+    assert_eq!(outcome.output(), "complex calculation is 2\n");
+    assert!(outcome.into_result().is_ok());
+
+    let outcome = manager.execute("help");
+    assert_eq!(outcome.output(), "calculate\nis-running\nstart\nstop\n");
+
+    let outcome = manager.execute("help calculate extra");
+    assert_eq!(outcome.output(), "");
+    assert!(matches!(
+        outcome.result(),
+        Err(CliError::InvalidCommand { command, .. }) if command == "calculate extra"
+    ));
+
+    let outcome = manager.execute("strat");
+    assert_eq!(outcome.output(), "");
+    assert!(matches!(outcome.result(), Err(CliError::InvalidCommand { command, .. }) if command == "strat"));
+
+    let outcome = manager.execute("start 'unterminated");
+    assert!(matches!(outcome.result(), Err(CliError::SyntaxError(_))));
+
+    let outcome = manager.execute("   ");
+    assert_eq!(outcome.output(), "");
+    assert!(outcome.result().is_ok());
+
+    assert!(manager.execute("EXIT").is_exit());
+}
+
+#[test]
+fn test_completion() {
+    let mut manager = CliManager::with_reader_writer(BufReader::new(&b""[..]), Vec::new());
+    manager
+        .add_handler(Arc::new(TestHandler::new()))
+        .expect("failed to add handler");
+    manager
+        .add_handler(Arc::new(SpecHandler))
+        .expect("failed to add handler");
+
+    let completions = manager.complete("st");
+    assert_eq!(completions.start(), 0);
+    assert_eq!(completions.candidates(), ["start", "stop"]);
+    assert_eq!(completions.common_prefix(), Some("st"));
+
+    let completions = manager.complete("");
+    assert_eq!(completions.candidates().len(), 16);
+    assert!(completions.candidates().contains(&String::from("help")));
+    assert!(completions.candidates().contains(&String::from("exit")));
+    assert!(completions.candidates().contains(&String::from("history")));
+
+    let completions = manager.complete("help t");
+    assert_eq!(completions.start(), 5);
+    assert_eq!(completions.candidates(), ["toggle"]);
+
+    let completions = manager.complete("scale 1.5 2 ");
+    assert_eq!(completions.start(), 12);
+    assert_eq!(completions.candidates(), ["cm", "m", "mm"]);
+    assert_eq!(completions.common_prefix(), Some(""));
+
+    assert_eq!(manager.complete("toggle o").candidates(), ["off", "on"]);
+    assert_eq!(
+        manager.complete("report --").candidates(),
+        ["--count", "--tag", "--verbose"]
+    );
+    assert!(manager.complete("scale --").candidates().is_empty());
+    assert!(manager.complete("calculate ").candidates().is_empty());
+    assert!(manager.complete("missing ").candidates().is_empty());
+
+    let completions = manager.complete("report -v \"daily r");
+    assert_eq!(completions.start(), 10);
+    assert_eq!(completions.candidates(), ["daily report"]);
+    assert_eq!(quote(&completions.candidates()[0]), "'daily report'");
+    assert_eq!(manager.complete("report 'daily\\ ").candidates(), Vec::<String>::new());
+    assert_eq!(manager.complete("report daily\\ r").candidates(), ["daily report"]);
+
+    let out_stream = TestBuffer::default();
+    let mut manager = CliManager::with_reader_writer(BufReader::new(&b""[..]), out_stream.clone());
+    manager
+        .add_handler(Arc::new(SpecHandler))
+        .expect("failed to add handler");
+    let args = ["tool", COMPLETE_COMMAND, "scale", "1", "2", "c"];
+    assert_eq!(
+        manager.run_args(args.iter().cloned()).expect("failed to run"),
+        EXIT_SUCCESS
+    );
+    assert_eq!(out_stream.buffer.lock().unwrap().as_slice(), b"cm\n");
+
+    let script = bash_completion_script("my-tool");
+    assert!(script.contains("\"my-tool\" __complete"));
+    assert!(script.contains("complete -o filenames -F _my_tool_complete my-tool"));
+}
+
+#[test]
+fn test_quote() {
+    for text in ["plain", "", "two words", "it's", "\"quoted\"", "back\\slash"].iter() {
+        assert_eq!(tokenize(&quote(text)).unwrap(), [*text], "text: {}", text);
+    }
+    assert_eq!(quote("plain"), "plain");
+    assert_eq!(quote("it's"), "'it'\\''s'");
+    assert_eq!(quote("a;b"), "'a;b'");
+    assert_eq!(
+        split_commands(&quote("a && b")).unwrap(),
+        [(Operator::Sequence, vec![Command::from(vec![String::from("a && b")])])]
+    );
+    assert_eq!(quote("a>b"), "'a>b'");
+    assert_eq!(quote("$host"), "'$host'");
+}
+
+#[test]
+fn test_line_editor() {
+    let cases: [(&str, &[&str]); 11] = [
+        ("abc\x1b[D\x1b[DX\r", &["aXbc"]),
+        ("world\x01hello \x05!\r", &["hello world!"]),
+        ("one two three\x17\x17four\r", &["one four"]),
+        ("one two\x1b[1;5Dnew \x1b[Hthe \x1b[F\r", &["the one new two"]),
+        ("discard this\x15keep\x01\x1b[3~\x0b\r", &[""]),
+        (
+            "first\rsecond\r\x1b[A\x1b[A\r\x10\x10\x0e\r",
+            &["first", "second", "first", "first"],
+        ),
+        ("draft\x1b[A\x1b[B\r", &["draft"]),
+        (
+            "deploy prod\rstatus\rdeploy test\r\x12dep\x12\r\x12st\x07\x12stat\x1b[C\r",
+            &["deploy prod", "status", "deploy test", "deploy prod", "status"],
+        ),
+        ("partial\x03caf\u{e9}\r\x04", &["", "caf\u{e9}"]),
+        ("ab\x1bcd\r", &["abcd"]),
+        ("status\r\x12st\x1bx\r", &["status", "x"]),
+    ];
+    for (input, expected) in cases.iter() {
+        let mut editor = LineEditor::new(input.as_bytes(), Vec::new());
+        let lines: Vec<String> = editor.by_ref().lines().map(Result::unwrap).collect();
+        assert_eq!(lines, *expected, "input: {:?}", input);
+    }
+
+    let mut editor = LineEditor::new(&b"ls\r\rls\rpwd\rcd\r"[..], Vec::new());
+    editor.set_history_size(2);
+    assert_eq!(editor.by_ref().lines().count(), 5);
+    assert_eq!(editor.history(), ["pwd", "cd"]);
+
+    let mut editor = LineEditor::new(&b"a\x1b[Db\r\nc"[..], Vec::new());
+    editor.set_editing(false);
+    assert!(!editor.is_editing());
+    let lines: Vec<String> = editor.by_ref().lines().map(Result::unwrap).collect();
+    assert_eq!(lines, ["a\x1b[Db", "c"]);
+    assert!(editor.history().is_empty());
+
+    let keys = Keystrokes(vec!["ab", "\x1b[D", "\x1b", "x", "\x1bOH", "c", "\x1b[1;5C", "!", "\r"]);
+    let mut editor = LineEditor::new(keys, Vec::new());
+    let lines: Vec<String> = editor.by_ref().lines().map(Result::unwrap).collect();
+    assert_eq!(lines, ["caxb!"]);
+
+    let completions = [
+        (
+            "cat my\t\r",
+            Completions::new(4, vec![String::from("my file")]),
+            "cat 'my file' \n",
+        ),
+        (
+            "cat sub\t\r",
+            Completions::new(4, vec![String::from("subdir/")]),
+            "cat subdir/\n",
+        ),
+        (
+            "cat a\t\r",
+            Completions::new(4, vec![String::from("alpha"), String::from("alpine")]),
+            "cat alp\n",
+        ),
+        (
+            "cat al\x1b[Dx\x1b[C\t\r",
+            Completions::new(4, vec![String::from("alpha"), String::from("beta")]),
+            "cat axl\n",
+        ),
+    ];
+    for (input, completions, expected) in completions.iter() {
+        let mut editor = LineEditor::new(input.as_bytes(), Vec::new());
+        let mut line = String::new();
+        let len = editor
+            .read_line_with(&mut line, &[], &mut |_| completions.clone())
+            .expect("failed to read line");
+        assert_eq!((len, line.as_str()), (expected.len(), *expected), "input: {:?}", input);
+    }
+
+    let out_stream = TestBuffer::default();
+    let input = b"strt\x1b[D\x1b[Da\r\x1b[A\x1b[A\r\x1b[A\x15sto\t\rexit\r";
+    let mut manager = CliManager::with_editor_writer(LineEditor::new(&input[..], Vec::new()), out_stream.clone());
+    manager
+        .add_handler(Arc::new(TestHandler::new()))
+        .expect("failed to add handler");
+    assert_eq!(manager.execute("calculate total 1 + 2").output(), "");
+    assert_eq!(manager.start().expect("manager failed"), ExitReason::Exit);
+    let output = String::from_utf8(out_stream.buffer.lock().unwrap().clone()).unwrap();
+    assert_eq!(output, "> started\n> total is 3\n> stopped\n> ");
+    assert_eq!(
+        manager.history().entries(),
+        [
+            "calculate total 1 + 2",
+            "start",
+            "calculate total 1 + 2",
+            "stop ",
+            "exit"
+        ]
+    );
+}
+
+#[test]
+fn test_history() {
+    let path = std::env::temp_dir().join(format!("jdn-cli-history-{}.txt", std::process::id()));
+    std::fs::write(&path, "stop\nstart\n\n").expect("failed to write history");
+
+    let mut manager = CliManager::with_reader_writer(BufReader::new(&b""[..]), Vec::new());
+    manager
+        .add_handler(Arc::new(TestHandler::new()))
+        .expect("failed to add handler");
+    manager.set_history(History::with_file(&path, 4).expect("failed to load history"));
+    assert_eq!(manager.history().entries(), ["stop", "start"]);
+
+    assert_eq!(manager.execute("!!").output(), "start\nstarted\n");
+    assert_eq!(manager.execute("calculate total 1 + 2").output(), "total is 3\n");
+    assert_eq!(manager.execute("  ").output(), "");
+    assert_eq!(manager.execute("!-2").output(), "start\nstarted\n");
+    assert_eq!(
+        manager.execute("history").output(),
+        "1  start\n2  calculate total 1 + 2\n3  start\n4  history\n"
+    );
+    assert_eq!(manager.execute("!2").output(), "calculate total 1 + 2\ntotal is 3\n");
+    assert_eq!(
+        manager.execute("history 2").output(),
+        "3  calculate total 1 + 2\n4  history 2\n"
+    );
+
+    let outcome = manager.execute("!9");
+    assert!(matches!(outcome.result(), Err(CliError::ExecutionError(message)) if message == "!9: event not found"));
+    assert!(matches!(
+        manager.execute("history x").result(),
+        Err(CliError::ArgumentParseFailure(_))
+    ));
+    assert!(matches!(
+        manager.execute("history 1 2").result(),
+        Err(CliError::InvalidNumberOfArguments { .. })
+    ));
+
+    let saved = std::fs::read_to_string(&path).expect("failed to read history");
+    assert_eq!(saved, "calculate total 1 + 2\nhistory 2\nhistory x\nhistory 1 2\n");
+    let history = History::with_file(&path, 2).expect("failed to load history");
+    assert_eq!(history.entries(), ["history x", "history 1 2"]);
+    std::fs::remove_file(&path).expect("failed to remove history");
+
+    let mut manager = CliManager::with_reader_writer(BufReader::new(&b""[..]), Vec::new());
+    assert!(manager.execute("!!").result().is_err());
+    assert!(manager
+        .execute("help history")
+        .output()
+        .starts_with("Usage: history [count]\n"));
+    assert!(manager.history().file().is_none());
+}
+
+#[test]
+fn test_registration() {
+    let mut manager = CliManager::with_reader_writer(BufReader::new(&b""[..]), Vec::new());
+    manager
+        .add_handler(Arc::new(TestHandler::new()))
+        .expect("failed to add handler");
+
+    let error = manager.add_handler(Arc::new(TestHandler2::new())).unwrap_err();
+    assert_eq!(
+        error,
+        RegistrationError::Conflict {
+            command: String::from("start"),
+            existing: String::from("integration_tests::TestHandler"),
+            handler: String::from("integration_tests::TestHandler2"),
+        }
+    );
+    assert_eq!(
+        error.to_string(),
+        "Command 'start' of integration_tests::TestHandler2 is already handled by integration_tests::TestHandler"
+    );
+    assert!(manager.execute("end").result().is_err());
+    assert_eq!(manager.execute("start").output(), "started\n");
+
+    manager
+        .replace_handler(Arc::new(TestHandler2::new()))
+        .expect("failed to replace handler");
+    assert_eq!(manager.execute("start").output(), "begun\n");
+    assert_eq!(manager.execute("end").output(), "ended\n");
+    assert_eq!(manager.execute("stop").output(), "stopped\n");
+
+    let reserved_commands = [
+        "Help",
+        "exit",
+        "history",
+        "echo",
+        "alias",
+        "unalias",
+        "set",
+        "UNSET",
+        "vars",
+        COMPLETE_COMMAND,
+    ];
+    for reserved in reserved_commands.iter() {
+        let handler = ReservedHandler(reserved);
+        let error = RegistrationError::Reserved {
+            command: reserved.to_string(),
+            handler: String::from("reserved"),
+        };
+        assert_eq!(manager.add_handler(Arc::new(handler)), Err(error.clone()));
+        assert_eq!(manager.replace_handler(Arc::new(handler)), Err(error));
+    }
+    assert!(matches!(
+        manager.execute("apply").result(),
+        Err(CliError::InvalidCommand { .. })
+    ));
+}
+
+#[test]
+fn test_unregister() {
+    let mut manager = CliManager::with_reader_writer(BufReader::new(&b""[..]), Vec::new());
+    let handler: Arc<dyn CliHandler> = Arc::new(TestHandler::new());
+    let handler_id = manager
+        .add_handler(Arc::clone(&handler))
+        .expect("failed to add handler");
+    let spec_id = manager
+        .add_handler(Arc::new(SpecHandler))
+        .expect("failed to add handler");
+    assert_ne!(handler_id, spec_id);
+
+    assert!(manager.remove_command("is-running"));
+    assert!(!manager.remove_command("is-running"));
+    assert!(matches!(
+        manager.execute("is-running").result(),
+        Err(CliError::InvalidCommand { .. })
+    ));
+    assert_eq!(manager.execute("start").output(), "started\n");
+
+    assert!(manager.remove_handler(&handler));
+    assert!(!manager.remove_handler(&handler));
+    assert!(!manager.remove_handler_id(handler_id));
+    assert_eq!(manager.execute("help").output(), "report\nscale\ntoggle\n");
+
+    let registry = manager.registry();
+    thread::spawn(move || {
+        assert!(registry.remove_handler_id(spec_id));
+        registry
+            .add_handler(Arc::new(TestHandler2::new()))
+            .expect("failed to add handler");
+    })
+    .join()
+    .expect("registry thread failed");
+    assert_eq!(manager.execute("help").output(), "end\nstart\n");
+    assert_eq!(manager.registry().commands(), ["end", "start"]);
+    assert_eq!(manager.execute("start").output(), "begun\n");
+    assert!(manager.execute("toggle on").result().is_err());
+}
+
+#[test]
+fn test_dynamic_commands() {
+    let mut manager = CliManager::with_reader_writer(BufReader::new(&b""[..]), Vec::new());
+    manager
+        .add_handler(Arc::new(TestHandler::new()))
+        .expect("failed to add handler");
+    let devices = Arc::new(DeviceHandler::new(true));
+    manager.add_handler(devices.clone()).expect("failed to add handler");
+    assert!(matches!(
+        manager.execute("lamp").result(),
+        Err(CliError::InvalidCommand { .. })
+    ));
+
+    devices.attach(&["lamp", "fan", "start", "exit"]);
+    assert_eq!(manager.execute("lamp").output(), "lamp ok\n");
+    assert_eq!(manager.execute("start").output(), "started\n");
+    assert!(manager.execute("exit").is_exit());
+    assert_eq!(
+        manager.execute("help").output(),
+        "calculate\nfan\nis-running\nlamp\nstart\nstop\n"
+    );
+    assert_eq!(manager.complete("la").candidates(), ["lamp"]);
+
+    devices.detach("lamp");
+    assert!(matches!(
+        manager.execute("lamp").result(),
+        Err(CliError::InvalidCommand { .. })
+    ));
+    assert!(!manager.remove_command("fan"));
+    let handler: Arc<dyn CliHandler> = devices;
+    assert!(manager.remove_handler(&handler));
+    assert!(manager.execute("fan").result().is_err());
+
+    let plugins = Arc::new(DeviceHandler::new(false));
+    plugins.attach(&["load"]);
+    let id = manager.add_handler(plugins.clone()).expect("failed to add handler");
+    plugins.attach(&["unload"]);
+    assert!(manager.execute("unload").result().is_err());
+    let registry = manager.registry();
+    assert_eq!(registry.refresh_handler(id), Ok(true));
+    assert_eq!(manager.execute("unload").output(), "unload ok\n");
+
+    plugins.detach("load");
+    plugins.attach(&["stop"]);
+    assert!(matches!(
+        registry.refresh_handler(id),
+        Err(RegistrationError::Conflict { command, .. }) if command == "stop"
+    ));
+    assert_eq!(manager.execute("load").output(), "load ok\n");
+    plugins.detach("stop");
+    assert_eq!(registry.refresh_handler(id), Ok(true));
+    assert!(manager.execute("load").result().is_err());
+
+    plugins.detach("load");
+    plugins.detach("unload");
+    assert_eq!(registry.refresh_handler(id), Ok(true));
+    assert!(manager.execute("unload").result().is_err());
+    plugins.attach(&["unload"]);
+    assert_eq!(registry.refresh_handler(id), Ok(true));
+    assert_eq!(manager.execute("unload").output(), "unload ok\n");
+
+    assert!(manager.remove_handler_id(id));
+    assert_eq!(registry.refresh_handler(id), Ok(false));
+    assert!(manager.execute("unload").result().is_err());
+
+    let empty = Arc::new(DeviceHandler::new(false));
+    let id = manager.add_handler(empty.clone()).expect("failed to add handler");
+    empty.attach(&["scan"]);
+    assert_eq!(registry.refresh_handler(id), Ok(true));
+    assert_eq!(manager.execute("scan").output(), "scan ok\n");
+    empty.detach("scan");
+    assert_eq!(registry.refresh_handler(id), Ok(true));
+    assert!(manager.remove_handler_id(id));
+    assert!(!manager.remove_handler_id(id));
+}
+
+#[test]
+fn test_subcommands() {
+    let mut manager = CliManager::with_reader_writer(BufReader::new(&b""[..]), Vec::new());
+    manager.add_handler(Arc::new(Database)).expect("failed to add handler");
+
+    assert_eq!(
+        manager.execute("db backup create /tmp/db").output(),
+        "created /tmp/db\n"
+    );
+    assert_eq!(manager.execute("db backup list").output(), "daily\nweekly\n");
+    assert_eq!(manager.execute("db migrate").output(), "migrated to latest\n");
+    assert_eq!(manager.execute("db migrate 3").output(), "migrated to 3\n");
+    assert!(matches!(
+        manager.execute("db backup create").result(),
+        Err(CliError::InvalidNumberOfArguments { .. })
+    ));
+    for (line, command) in [
+        ("db", "db"),
+        ("db backup", "db backup"),
+        ("db backup drop now", "db backup drop"),
+        ("dbx backup", "dbx"),
+    ]
+    .iter()
+    {
+        let outcome = manager.execute(line);
+        assert!(
+            matches!(outcome.result(), Err(CliError::InvalidCommand { command: c, .. }) if c == command),
+            "line: {}",
+            line
+        );
+    }
+
+    assert_eq!(manager.execute("help").output(), "db  Subcommands: backup, migrate\n");
+    assert_eq!(
+        manager.execute("help db").output(),
+        "db backup   Subcommands: create, list\ndb migrate  Applies pending migrations.\n"
+    );
+    assert_eq!(
+        manager.execute("help db backup").output(),
+        "db backup create  Creates a backup at the given path.\ndb backup list    Lists the backups.\n"
+    );
+    assert_eq!(
+        manager.execute("help db backup create").output(),
+        "Usage: db backup create <path>\nCreates a backup at the given path.\n"
+    );
+    assert!(matches!(
+        manager.execute("help db drop").result(),
+        Err(CliError::InvalidCommand { command, .. }) if command == "db drop"
+    ));
+
+    assert_eq!(manager.complete("d").candidates(), ["db"]);
+    assert_eq!(manager.complete("db ").candidates(), ["backup", "migrate"]);
+    assert_eq!(manager.complete("db backup c").candidates(), ["create"]);
+    assert_eq!(manager.complete("help db b").candidates(), ["backup"]);
+
+    let devices = DeviceHandler::new(false);
+    devices.attach(&["db", "exit now"]);
+    let devices = Arc::new(devices);
+    assert!(matches!(
+        manager.add_handler(devices.clone()),
+        Err(RegistrationError::Reserved { command, .. }) if command == "exit now"
+    ));
+    devices.detach("exit now");
+    manager.add_handler(devices).expect("failed to add handler");
+    assert_eq!(manager.execute("db").output(), "db ok\n");
+    assert_eq!(manager.execute("db migrate").output(), "migrated to latest\n");
+    assert_eq!(
+        manager.execute("help db").output(),
+        "Usage: db\nSubcommands:\ndb backup   Subcommands: create, list\ndb migrate  Applies pending migrations.\n"
+    );
+    assert_eq!(manager.execute("help").output(), "db\n");
+}
+
+#[test]
+fn test_dispatch_policy() {
+    let mut manager = CliManager::with_reader_writer(BufReader::new(&b""[..]), Vec::new());
+    manager
+        .add_handler(Arc::new(TestHandler::new()))
+        .expect("failed to add handler");
+    manager.add_handler(Arc::new(Database)).expect("failed to add handler");
+    assert_eq!(manager.dispatch_policy(), DispatchPolicy::default());
+
+    assert_eq!(manager.execute("START").output(), "started\n");
+    assert_eq!(manager.execute("DB Backup LIST").output(), "daily\nweekly\n");
+    assert!(manager.execute("Exit").is_exit());
+    assert!(matches!(
+        manager.execute("calc").result(),
+        Err(CliError::InvalidCommand { command, .. }) if command == "calc"
+    ));
+
+    manager.set_dispatch_policy(DispatchPolicy {
+        case_sensitive: false,
+        allow_prefix: true,
+    });
+    assert_eq!(manager.execute("sta").output(), "started\n");
+    assert_eq!(manager.execute("db back l").output(), "daily\nweekly\n");
+    assert_eq!(manager.execute("DB BACK C /tmp/db").output(), "created /tmp/db\n");
+    assert_eq!(manager.execute("db m 3").output(), "migrated to 3\n");
+    assert_eq!(
+        manager.execute("help db back").output(),
+        manager.execute("help db backup").output()
+    );
+    assert!(manager.execute("hist").result().is_ok());
+    assert!(manager.execute("ex").is_exit());
+    let outcome = manager.execute("st");
+    assert!(matches!(
+        outcome.result(),
+        Err(CliError::AmbiguousCommand { command, candidates }) if command == "st" && candidates == &["start", "stop"]
+    ));
+    assert_eq!(
+        outcome.result().as_ref().unwrap_err().to_string(),
+        "Ambiguous command 'st': could be 'start' or 'stop'"
+    );
+    assert_eq!(outcome.into_result().unwrap_err().exit_code(), 127);
+    assert_eq!(
+        manager.execute("h").result().as_ref().unwrap_err().to_string(),
+        "Ambiguous command 'h': could be 'help' or 'history'"
+    );
+    assert_eq!(manager.complete("DB back").candidates(), ["backup"]);
+
+    manager.set_dispatch_policy(DispatchPolicy {
+        case_sensitive: true,
+        allow_prefix: false,
+    });
+    assert_eq!(manager.execute("start").output(), "started\n");
+    assert!(!manager.execute("EXIT").is_exit());
+    assert_eq!(
+        manager.execute("START").result().as_ref().unwrap_err().to_string(),
+        "Invalid command 'START'. Did you mean 'start'?"
+    );
+    assert_eq!(
+        manager.execute("db Migrate").result().as_ref().unwrap_err().to_string(),
+        "Invalid command 'db Migrate'. Did you mean 'db migrate'?"
+    );
+    assert!(manager.complete("DB ").candidates().is_empty());
+}
+
+#[test]
+fn test_suggestions() {
+    let mut manager = CliManager::with_reader_writer(BufReader::new(&b""[..]), Vec::new());
+    manager
+        .add_handler(Arc::new(TestHandler::new()))
+        .expect("failed to add handler");
+    manager.add_handler(Arc::new(Database)).expect("failed to add handler");
+
+    let outcome = manager.execute("strat");
+    assert!(matches!(
+        outcome.result(),
+        Err(CliError::InvalidCommand { command, suggestions }) if command == "strat" && suggestions == &["start"]
+    ));
+    assert_eq!(
+        outcome.result().as_ref().unwrap_err().to_string(),
+        "Invalid command 'strat'. Did you mean 'start'?"
+    );
+    assert_eq!(
+        manager.execute("sta").result().as_ref().unwrap_err().to_string(),
+        "Invalid command 'sta'. Did you mean 'set', 'start' or 'stop'?"
+    );
+    assert_eq!(manager.suggest("STP"), vec!["stop", "set"]);
+    assert_eq!(manager.suggest("hepl"), vec!["help"]);
+    assert_eq!(manager.suggest("db backp"), vec!["db backup"]);
+    assert_eq!(manager.suggest("db mgrate"), vec!["db migrate"]);
+    assert_eq!(manager.suggest("start"), Vec::<String>::new());
+    assert_eq!(manager.suggest("unrelated"), Vec::<String>::new());
+    assert_eq!(
+        manager.execute("invalid").result().as_ref().unwrap_err().to_string(),
+        "Invalid command 'invalid'"
+    );
+
+    manager.set_suggestion_distance(0);
+    assert_eq!(manager.suggest("strat"), Vec::<String>::new());
+    assert_eq!(
+        manager.execute("strat").result().as_ref().unwrap_err().to_string(),
+        "Invalid command 'strat'"
+    );
+}
+
+#[test]
+fn test_aliases() {
+    let mut manager = CliManager::with_reader_writer(BufReader::new(&b""[..]), Vec::new());
+    manager
+        .add_handler(Arc::new(TestHandler::new()))
+        .expect("failed to add handler");
+    manager.add_handler(Arc::new(Lister)).expect("failed to add handler");
+
+    assert_eq!(ListCommand::aliases("list"), vec!["dir", "entries"]);
+    assert_eq!(
+        manager.execute("dir -l").output(),
+        "list long=true all=false sort=None []\n"
+    );
+    assert_eq!(
+        manager.execute("ENTRIES").output(),
+        "list long=false all=false sort=None []\n"
+    );
+    assert_eq!(
+        manager.execute("list a --sort=name -- -l").output(),
+        "list long=false all=false sort=Some(\"name\") [\"a\", \"-l\"]\n"
+    );
+    assert_eq!(
+        manager.execute("list -x").result().as_ref().unwrap_err().to_string(),
+        "Argument parse failure: unknown option '-x'"
+    );
+    assert_eq!(manager.complete("list --").candidates(), ["--all", "--long", "--sort"]);
+    assert!(manager
+        .execute("help")
+        .output()
+        .contains("list (dir, entries)  Lists the entries.\n"));
+    assert_eq!(
+        manager.execute("help dir").output(),
+        "Usage: list [-l|--long] [-a|--all] [-s|--sort <sort>] [paths...]\nLists the entries.\nAliases: dir, entries\n"
+    );
+
+    assert!(manager.execute("alias ls=list --all").result().is_ok());
+    assert!(manager.execute("alias go='start now'").result().is_ok());
+    assert!(manager.execute("alias halt=stop").result().is_ok());
+    assert!(manager.execute("alias d=dir").result().is_ok());
+    assert_eq!(manager.aliases().get("ls"), Some("list --all"));
+    assert_eq!(
+        manager.execute("ls -l").output(),
+        "list long=true all=true sort=None []\n"
+    );
+    assert_eq!(manager.execute("go").output(), "started\n");
+    assert_eq!(
+        manager.execute("d").output(),
+        "list long=false all=false sort=None []\n"
+    );
+    assert_eq!(
+        manager.execute("alias").output(),
+        "alias d=dir\nalias go='start now'\nalias halt=stop\nalias ls='list --all'\n"
+    );
+    assert_eq!(manager.execute("alias ls").output(), "alias ls='list --all'\n");
+    assert_eq!(manager.execute("help ls").output(), "ls is an alias for 'list --all'\n");
+    assert!(manager.execute("help").output().contains("stop (halt)\n"));
+    assert_eq!(manager.complete("l").candidates(), ["list", "ls"]);
+
+    assert!(manager.execute("alias rs='stop; go'").result().is_ok());
+    assert_eq!(manager.execute("alias rs").output(), "alias rs='stop; go'\n");
+    assert_eq!(manager.execute("rs").output(), "stopped\nstarted\n");
+    assert_eq!(manager.execute("rs && echo done").output(), "stopped\nstarted\ndone\n");
+    assert_eq!(
+        manager.execute("echo begin; rs || echo failed").output(),
+        "begin\nstopped\nstarted\n"
+    );
+    assert_eq!(manager.run_args(["app", "rs"].iter().copied()).ok(), Some(EXIT_SUCCESS));
+
+    assert!(manager.execute("alias ping=pong").result().is_ok());
+    assert!(manager.execute("alias pong=ping").result().is_ok());
+    assert!(matches!(
+        manager.execute("ping").result(),
+        Err(CliError::InvalidCommand { command, .. }) if command == "ping"
+    ));
+    for (line, message) in [
+        ("alias help=list", "Execution error: help: reserved by the manager"),
+        (
+            "alias =list",
+            "Argument parse failure: invalid value '=list' for <name>=<command>",
+        ),
+        (
+            "alias x='a\nb'",
+            "Argument parse failure: invalid value 'x=a\nb' for <name>=<command>",
+        ),
+        ("alias a b", "Invalid number of arguments: expected 0-1, received 2."),
+        ("alias missing", "Execution error: missing: alias not found"),
+        ("unalias missing", "Execution error: missing: alias not found"),
+        ("unalias", "Invalid number of arguments: expected 1, received 0."),
+    ]
+    .iter()
+    {
+        let outcome = manager.execute(line);
+        assert_eq!(
+            outcome.result().as_ref().unwrap_err().to_string(),
+            *message,
+            "line: {}",
+            line
+        );
+    }
+
+    assert!(manager.execute("unalias ls ping pong").result().is_ok());
+    assert_eq!(manager.aliases().get("ls"), None);
+    assert!(matches!(
+        manager.execute("ls").result(),
+        Err(CliError::InvalidCommand { command, .. }) if command == "ls"
+    ));
+
+    let path = std::env::temp_dir().join(format!("jdn-cli-aliases-{}.txt", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    manager.set_aliases(Aliases::with_file(&path).expect("failed to load aliases"));
+    assert!(manager.execute("alias ll=list -l").result().is_ok());
+    assert!(manager.execute("alias la=list -a").result().is_ok());
+    assert!(manager.execute("unalias la").result().is_ok());
+    assert!(manager.execute("alias lb='list\r\nbroken=list'").result().is_err());
+    assert_eq!(
+        std::fs::read_to_string(&path).expect("failed to read aliases"),
+        "ll=list -l\n"
+    );
+    let aliases = Aliases::with_file(&path).expect("failed to load aliases");
+    assert_eq!(aliases.get("ll"), Some("list -l"));
+    assert_eq!(aliases.file(), Some(path.as_path()));
+    let mut aliases = Aliases::new();
+    for (name, command) in [("a=b", "list"), ("", "list"), ("a\nb", "list"), ("a", "list\nb=c")] {
+        let error = aliases.set(name, command).expect_err("invalid alias was defined");
+        assert_eq!(error.kind(), ErrorKind::InvalidInput);
+    }
+    assert!(aliases.is_empty());
+    std::fs::remove_file(&path).expect("failed to remove aliases");
+}
+
+#[test]
+fn test_write_failure() {
+    let reader = BufReader::new(&b"help\n"[..]);
+    let mut manager = CliManager::with_reader_writer(reader, FailingStream);
+    match manager.start() {
+        Err(CliIoError::Write(e)) => assert_eq!(e.kind(), ErrorKind::BrokenPipe),
+        other => panic!("expected write failure, received {:?}", other),
+    }
+}
+
+#[test]
+fn test_read_failure() {
+    let mut manager = CliManager::with_reader_writer(BufReader::new(FailingStream), Vec::new());
+    match manager.start() {
+        Err(CliIoError::Read(e)) => assert_eq!(e.kind(), ErrorKind::BrokenPipe),
+        other => panic!("expected read failure, received {:?}", other),
+    }
+}
+
 fn print(buf: Vec<u8>, expect: Option<String>) -> std::io::Result<()> {
     let msg = String::from_utf8(buf).unwrap();
     if let Some(expect) = expect {
@@ -415,12 +2213,20 @@ fn print(buf: Vec<u8>, expect: Option<String>) -> std::io::Result<()> {
 #[derive(Default)]
 struct TestBuffer {
     buffer: Arc<Mutex<Vec<u8>>>,
+    closed: Arc<AtomicBool>,
+}
+
+impl TestBuffer {
+    fn close(&self) {
+        self.closed.store(true, Ordering::SeqCst);
+    }
 }
 
 impl Clone for TestBuffer {
     fn clone(&self) -> Self {
         TestBuffer {
             buffer: Arc::clone(&self.buffer),
+            closed: Arc::clone(&self.closed),
         }
     }
 }
@@ -428,7 +2234,7 @@ impl Clone for TestBuffer {
 impl Read for TestBuffer {
     fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
         loop {
-            let mut contents = self
+            let contents = self
                 .buffer
                 .lock()
                 .expect("Unable to lock read buffer")
@@ -436,8 +2242,10 @@ impl Read for TestBuffer {
                 .collect::<Vec<u8>>();
             let len = contents.len();
             if len > 0 {
-                buf[..len].copy_from_slice(&mut contents);
+                buf[..len].copy_from_slice(&contents);
                 return Ok(len);
+            } else if self.closed.load(Ordering::SeqCst) {
+                return Ok(0);
             } else {
                 thread::sleep(Duration::from_millis(50));
             }
@@ -457,6 +2265,39 @@ impl Write for TestBuffer {
     }
 }
 
+struct FailingStream;
+
+impl Read for FailingStream {
+    fn read(&mut self, _buf: &mut [u8]) -> std::io::Result<usize> {
+        Err(Error::from(ErrorKind::BrokenPipe))
+    }
+}
+
+impl Write for FailingStream {
+    fn write(&mut self, _buf: &[u8]) -> std::io::Result<usize> {
+        Err(Error::from(ErrorKind::BrokenPipe))
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Err(Error::from(ErrorKind::BrokenPipe))
+    }
+}
+
+/// Delivers each key press in a single read, as a terminal does.
+struct Keystrokes(Vec<&'static str>);
+
+impl Read for Keystrokes {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.0.is_empty() {
+            return Ok(0);
+        }
+        let key = self.0.remove(0).as_bytes();
+        buf[..key.len()].copy_from_slice(key);
+        Ok(key.len())
+    }
+}
+
+#[derive(Default)]
 pub struct TestHandler {
     on: Mutex<Option<String>>,
 }
@@ -558,6 +2399,223 @@ impl CliHandler for TestHandler {
     }
 }
 
+pub struct DocumentedHandler;
+
+impl DocumentedHandler {
+    pub const BACKUP_COMMAND: &'static str = "backup";
+    pub const RESTORE_COMMAND: &'static str = "restore";
+}
+
+impl CliHandler for DocumentedHandler {
+    fn get_commands(&self) -> HashSet<&'static str> {
+        [Self::BACKUP_COMMAND, Self::RESTORE_COMMAND].iter().cloned().collect()
+    }
+
+    fn handle_command(&self, command: &str, _args: Vec<String>, writer: &mut dyn Write) -> Result<(), CliError> {
+        writeln!(writer, "{} complete", command).expect("Failed to write response");
+        Ok(())
+    }
+
+    fn get_summary(&self, command: &str) -> Option<String> {
+        match command {
+            Self::BACKUP_COMMAND => Some(String::from("Backs up the database")),
+            Self::RESTORE_COMMAND => Some(String::from("Restores the database")),
+            _ => None,
+        }
+    }
+
+    fn get_description(&self, command: &str) -> Option<String> {
+        match command {
+            Self::BACKUP_COMMAND => Some(String::from(
+                "Writes a snapshot of the database to the given path, optionally compressing it.",
+            )),
+            _ => None,
+        }
+    }
+
+    fn get_usage(&self, command: &str) -> Option<String> {
+        match command {
+            Self::BACKUP_COMMAND => Some(String::from("backup <path> [--compress]")),
+            _ => None,
+        }
+    }
+}
+
+pub struct SpecHandler;
+
+impl SpecHandler {
+    pub const SCALE_COMMAND: &'static str = "scale";
+    pub const TOGGLE_COMMAND: &'static str = "toggle";
+    pub const REPORT_COMMAND: &'static str = "report";
+}
+
+impl CliHandler for SpecHandler {
+    fn get_commands(&self) -> HashSet<&'static str> {
+        [Self::SCALE_COMMAND, Self::TOGGLE_COMMAND, Self::REPORT_COMMAND]
+            .iter()
+            .cloned()
+            .collect()
+    }
+
+    fn get_spec(&self, command: &str) -> Option<CommandSpec> {
+        match command {
+            Self::SCALE_COMMAND => Some(
+                CommandSpec::new()
+                    .arg(ArgSpec::new("value", ArgType::Float))
+                    .arg(ArgSpec::new("factor", ArgType::Int))
+                    .arg(ArgSpec::new("unit", ArgType::Choice(vec!["mm", "cm", "m"])).default_value("m"))
+                    .arg(ArgSpec::new("labels", ArgType::String).variadic()),
+            ),
+            Self::TOGGLE_COMMAND => Some(
+                CommandSpec::new()
+                    .arg(ArgSpec::new("enabled", ArgType::Bool))
+                    .arg(ArgSpec::new("path", ArgType::Path).optional()),
+            ),
+            Self::REPORT_COMMAND => Some(
+                CommandSpec::new()
+                    .option(OptionSpec::flag("verbose").short('v'))
+                    .option(OptionSpec::value("count", ArgType::Int).short('n'))
+                    .option(OptionSpec::value("tag", ArgType::String).short('t'))
+                    .arg(ArgSpec::new("title", ArgType::String))
+                    .arg(ArgSpec::new("rest", ArgType::String).variadic()),
+            ),
+            _ => None,
+        }
+    }
+
+    fn complete_argument(&self, command: &str, args: &[String], _partial: &str) -> Vec<String> {
+        let candidates: &[&str] = match (command, args.len()) {
+            (Self::SCALE_COMMAND, 2) => &["mm", "cm", "m"],
+            (Self::TOGGLE_COMMAND, 0) => &["true", "false", "on", "off"],
+            (Self::REPORT_COMMAND, _) => &["daily report", "weekly"],
+            _ => &[],
+        };
+        candidates.iter().map(|candidate| candidate.to_string()).collect()
+    }
+
+    fn handle_arguments(&self, command: &str, args: ParsedArgs, writer: &mut dyn Write) -> Result<(), CliError> {
+        match command {
+            Self::SCALE_COMMAND => {
+                let value = args.get_float("value").unwrap() * args.get_int("factor").unwrap() as f64;
+                let labels: Vec<String> = args.get_all("labels").iter().map(to_text).collect();
+                write!(writer, "{} {}", value, args.get_str("unit").unwrap()).expect("Failed to write scale response");
+                if !labels.is_empty() {
+                    write!(writer, " {}", labels.join(",")).expect("Failed to write scale response");
+                }
+                writeln!(writer).expect("Failed to write scale response");
+            }
+            Self::TOGGLE_COMMAND => {
+                let state = if args.get_bool("enabled").unwrap() {
+                    "enabled"
+                } else {
+                    "disabled"
+                };
+                match args.get_path("path") {
+                    Some(path) => writeln!(writer, "{} at {}", state, path.display()),
+                    None => writeln!(writer, "{}", state),
+                }
+                .expect("Failed to write toggle response");
+            }
+            Self::REPORT_COMMAND => {
+                let tags: Vec<String> = args.get_all("tag").iter().map(to_text).collect();
+                let rest: Vec<String> = args.get_all("rest").iter().map(to_text).collect();
+                writeln!(
+                    writer,
+                    "{} verbosity={} count={} tags={} rest={}",
+                    args.get_str("title").unwrap(),
+                    args.option_count("verbose"),
+                    args.get_int("count").unwrap_or(1),
+                    tags.join(","),
+                    rest.join(",")
+                )
+                .expect("Failed to write report response");
+            }
+            _ => return Err(CliError::ExecutionError(format!("Unknown command: {}", command))),
+        }
+        Ok(())
+    }
+}
+
+#[derive(jdn_cli_derive::CliCommands)]
+#[cli(handler = Counter)]
+pub enum CounterCommand {
+    /// Adds the given amount to the counter.
+    ///
+    /// Negative amounts are subtracted.
+    Add {
+        amount: i64,
+    },
+    /// Resets the counter, optionally to the given value.
+    Reset(Option<i64>),
+    /// Adds all of the given amounts
+    /// to the counter.
+    #[cli(name = "sum")]
+    AddAll {
+        amounts: Vec<i64>,
+    },
+    Show,
+}
+
+#[derive(Default)]
+pub struct Counter {
+    value: Mutex<i64>,
+}
+
+impl CliExecute<CounterCommand> for Counter {
+    fn execute(&self, command: CounterCommand, writer: &mut dyn Write) -> Result<(), CliError> {
+        let mut value = self.value.lock().expect("Unable to lock `value`");
+        match command {
+            CounterCommand::Add { amount } => *value += amount,
+            CounterCommand::Reset(reset) => *value = reset.unwrap_or_default(),
+            CounterCommand::AddAll { amounts } => *value += amounts.iter().sum::<i64>(),
+            CounterCommand::Show => writeln!(writer, "{}", value).expect("Failed to write show response"),
+        }
+        Ok(())
+    }
+}
+
+#[derive(jdn_cli_derive::CliCommands)]
+#[cli(handler = Database)]
+pub enum DatabaseCommand {
+    /// Creates a backup at the given path.
+    #[cli(name = "db backup create")]
+    BackupCreate { path: String },
+    /// Lists the backups.
+    #[cli(name = "db backup list")]
+    BackupList,
+    /// Applies pending migrations.
+    #[cli(name = "db migrate")]
+    Migrate { version: Option<u32> },
+}
+
+pub struct Database;
+
+impl CliExecute<DatabaseCommand> for Database {
+    fn execute(&self, command: DatabaseCommand, writer: &mut dyn Write) -> Result<(), CliError> {
+        match command {
+            DatabaseCommand::BackupCreate { path } => writeln!(writer, "created {}", path),
+            DatabaseCommand::BackupList => writeln!(writer, "daily\nweekly"),
+            DatabaseCommand::Migrate { version: Some(version) } => writeln!(writer, "migrated to {}", version),
+            DatabaseCommand::Migrate { version: None } => writeln!(writer, "migrated to latest"),
+        }
+        .map_err(|e| CliError::ExecutionError(e.to_string()))
+    }
+}
+
+#[derive(jdn_cli_derive::CliCommands, Debug, PartialEq)]
+pub struct CopyFile {
+    source: String,
+    destination: Option<String>,
+}
+
+fn to_text(value: &ArgValue) -> String {
+    match value {
+        ArgValue::String(value) => value.clone(),
+        other => format!("{:?}", other),
+    }
+}
+
+#[derive(Default)]
 pub struct TestHandler2 {
     on: Mutex<Option<bool>>,
 }
@@ -593,3 +2651,78 @@ impl CliHandler for TestHandler2 {
         }
     }
 }
+
+#[derive(Clone, Copy)]
+pub struct ReservedHandler(&'static str);
+
+impl CliHandler for ReservedHandler {
+    fn get_commands(&self) -> HashSet<&'static str> {
+        ["apply", self.0].iter().cloned().collect()
+    }
+
+    fn get_name(&self) -> &str {
+        "reserved"
+    }
+}
+
+pub struct DeviceHandler {
+    dynamic: bool,
+    devices: Mutex<HashSet<&'static str>>,
+}
+
+impl DeviceHandler {
+    pub fn new(dynamic: bool) -> Self {
+        DeviceHandler {
+            dynamic,
+            devices: Mutex::new(HashSet::new()),
+        }
+    }
+
+    pub fn attach(&self, devices: &[&'static str]) {
+        self.devices.lock().unwrap().extend(devices);
+    }
+
+    pub fn detach(&self, device: &str) {
+        self.devices.lock().unwrap().remove(device);
+    }
+}
+
+impl CliHandler for DeviceHandler {
+    fn get_commands(&self) -> HashSet<&'static str> {
+        self.devices.lock().unwrap().clone()
+    }
+
+    fn has_dynamic_commands(&self) -> bool {
+        self.dynamic
+    }
+
+    fn handle_command(&self, command: &str, _args: Vec<String>, writer: &mut dyn Write) -> Result<(), CliError> {
+        writeln!(writer, "{} ok", command).map_err(|e| CliError::ExecutionError(e.to_string()))
+    }
+}
+
+#[derive(jdn_cli_derive::CliCommands)]
+#[cli(handler = Lister)]
+pub enum ListCommand {
+    /// Lists the entries.
+    #[cli(alias = "dir", alias = "entries")]
+    List {
+        #[cli(flag, short = 'l')]
+        long: bool,
+        #[cli(flag, short = 'a')]
+        all: bool,
+        #[cli(option, short = 's')]
+        sort: Option<String>,
+        paths: Vec<String>,
+    },
+}
+
+pub struct Lister;
+
+impl CliExecute<ListCommand> for Lister {
+    fn execute(&self, command: ListCommand, writer: &mut dyn Write) -> Result<(), CliError> {
+        let ListCommand::List { long, all, sort, paths } = command;
+        writeln!(writer, "list long={} all={} sort={:?} {:?}", long, all, sort, paths)
+            .map_err(|e| CliError::ExecutionError(e.to_string()))
+    }
+}